@@ -1,22 +1,19 @@
 use std::env;
-use std::thread;
 
-use tftp::Server;
+use tftp::{BusyPolicy, Server};
 
 fn main() {
     let mut args = env::args().skip(1);
     let addr = args.next().unwrap();
     let wd = args.next().unwrap();
 
-    let server = Server::new(addr.clone(), wd).unwrap();
+    let server = Server::new(addr.clone(), wd)
+        .unwrap()
+        .max_transfers(32)
+        .when_busy(BusyPolicy::Queue);
     println!("Serving Trivial File Transfer Protocol (TFTP) @ {}", addr);
 
-    while let Ok(h) = server.serve() {
-        print!("Handling request...");
-
-        thread::spawn(|| match h.handle() {
-            Ok(()) => println!("OK"),
-            Err(e) => println!("FAIL: {:?}", e),
-        });
+    if let Err(e) = server.run() {
+        println!("FAIL: {:?}", e);
     }
 }
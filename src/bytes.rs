@@ -1,8 +1,8 @@
 use std::convert::AsRef;
-use std::ffi::{CStr, CString};
-use std::io::{self, ErrorKind};
 use std::mem::size_of;
 
+use crate::packet::DecodeError;
+
 pub trait FromBytes: Sized {
     type Error;
 
@@ -26,17 +26,21 @@ impl<T> Bytes<T> {
 }
 
 impl FromBytes for Bytes<u16> {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> io::Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
 
+        if bytes.len() < size_of::<u16>() {
+            return Err(DecodeError::Truncated);
+        }
+
         if bytes.len() > size_of::<u16>() {
-            return Err(ErrorKind::InvalidInput.into());
+            return Err(DecodeError::TrailingBytes);
         }
 
         let mut bs = [0u8; size_of::<u16>()];
-        bs.copy_from_slice(&bytes[..]);
+        bs.copy_from_slice(bytes);
         let be = u16::from_be_bytes(bs);
 
         Ok(Self(be))
@@ -51,24 +55,33 @@ impl IntoBytes for Bytes<u16> {
 }
 
 impl FromBytes for Bytes<String> {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> io::Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
-        let cstr = CStr::from_bytes_with_nul(bytes)
-            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
-        let s = cstr
-            .to_str()
-            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+
+        let nul = match bytes.first_nul_idx() {
+            Some(idx) if idx + 1 == bytes.len() => idx,
+            Some(_) => return Err(DecodeError::TrailingBytes),
+            None => return Err(DecodeError::MissingNul),
+        };
+
+        let s = std::str::from_utf8(&bytes[..nul]).map_err(|_| DecodeError::InvalidString)?;
 
         Ok(Self(s.to_string()))
     }
 }
 
 impl IntoBytes for Bytes<String> {
+    /// Anything after an interior NUL byte is dropped, since it could not
+    /// be told apart from the next field anyway.
     fn into_bytes(self) -> Vec<u8> {
-        let c = CString::new(self.0).unwrap();
-        c.into_bytes_with_nul()
+        let mut bytes = self.0.into_bytes();
+        if let Some(idx) = bytes.first_nul_idx() {
+            bytes.truncate(idx);
+        }
+        bytes.push(b'\0');
+        bytes
     }
 }
 
@@ -110,6 +123,13 @@ mod tests {
         assert_eq!(n, actual.into_inner());
     }
 
+    #[test]
+    fn test_from_bytes_u16_with_wrong_length() {
+        assert!(Bytes::<u16>::from_bytes([]).is_err());
+        assert!(Bytes::<u16>::from_bytes([1]).is_err());
+        assert!(Bytes::<u16>::from_bytes([1, 2, 3]).is_err());
+    }
+
     #[test]
     fn test_into_bytes_u16() {
         let n = 55u16;
@@ -129,5 +149,9 @@ mod tests {
         let b = Bytes::new("hello, world!".to_string());
         let actual = b.into_bytes();
         assert_eq!(b"hello, world!\0", &actual[..]);
+
+        let b = Bytes::new("hello\0world".to_string());
+        let actual = b.into_bytes();
+        assert_eq!(b"hello\0", &actual[..]);
     }
 }
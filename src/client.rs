@@ -1,15 +1,20 @@
 //! A client-side connection to a TFTP server. Implementors can use this
 //! to build a more fully-featured client application.
 
+use std::cmp;
 use std::io::{self, Read, Result, Write};
 use std::iter::Iterator;
 use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::Duration;
 
 use rand::Rng;
 
 use crate::bytes::{FromBytes, IntoBytes};
-use crate::connection::Connection;
-use crate::connection::MIN_PORT_NUMBER;
+use crate::connection::{is_timeout, reject_unknown_tid, timed_out, Connection, Settings};
+use crate::connection::{MIN_PORT_NUMBER, MIN_TIMEOUT};
+use crate::negotiate;
+use crate::netascii::{NetAsciiReader, NetAsciiWriter};
+use crate::packet::expect::{ExpectPacket, Peer};
 use crate::packet::*;
 
 /// The initial state for building a `Client`.
@@ -24,6 +29,8 @@ pub struct New {
 pub struct ConnectTo {
     server: Vec<SocketAddr>,
     socket: UdpSocket,
+    options: Options,
+    settings: Settings,
 }
 
 /// Builds a `Client`.
@@ -35,6 +42,8 @@ pub struct Builder<T> {
 pub struct Client {
     server: Vec<SocketAddr>,
     socket: UdpSocket,
+    options: Options,
+    settings: Settings,
 }
 
 impl Builder<New> {
@@ -57,6 +66,8 @@ impl Builder<New> {
         let data = ConnectTo {
             server: resolved,
             socket: self.data.socket,
+            options: Options::new(),
+            settings: Settings::default(),
         };
 
         Ok(Builder { data })
@@ -64,11 +75,98 @@ impl Builder<New> {
 }
 
 impl Builder<ConnectTo> {
+    /// Requests an option (RFC 2347) from the server.
+    ///
+    /// The server is free to ignore options it does not support, in which
+    /// case the transfer proceeds as if the option was never requested.
+    pub fn option<N: AsRef<str>, V: AsRef<str>>(mut self, name: N, value: V) -> Self {
+        self.data.options.push(name, value);
+        self
+    }
+
+    /// Requests that `Data` packets carry `size` bytes instead of the
+    /// default 512 (RFC 2348).
+    ///
+    /// Valid sizes range from 8 to 65464 bytes. The server may choose a
+    /// smaller size than the one requested.
+    pub fn block_size(self, size: u16) -> Self {
+        self.option(negotiate::BLKSIZE, size.to_string())
+    }
+
+    /// Exchanges the size of the file being transferred (RFC 2349).
+    ///
+    /// When reading a file, `size` should be 0 and the server answers with
+    /// the actual size, which is handed to the writer factory given to
+    /// `Client::get_with`. When writing a file, `size` is the size of the
+    /// file, which lets the server refuse it before any data is sent.
+    pub fn transfer_size(self, size: u64) -> Self {
+        self.option(negotiate::TSIZE, size.to_string())
+    }
+
+    /// Requests that both peers wait `secs` seconds (1 to 255) before
+    /// giving up on a packet, instead of the default 3 seconds (RFC 2349).
+    pub fn timeout(self, secs: u8) -> Self {
+        self.option(negotiate::TIMEOUT, secs.to_string())
+    }
+
+    /// Requests that up to `size` `Data` packets are sent before waiting for
+    /// an `Ack` (RFC 7440).
+    ///
+    /// Larger windows speed up transfers over links with a long round trip
+    /// time. The server may choose a smaller window than the one requested.
+    pub fn window_size(self, size: u16) -> Self {
+        self.option(negotiate::WINDOWSIZE, size.to_string())
+    }
+
+    /// Requests that block numbers wrap around to 0 or 1 after block 65535,
+    /// which matters for files larger than 65535 blocks.
+    ///
+    /// Without this, or if the server does not acknowledge it, block 65535
+    /// is followed by block 0.
+    pub fn rollover(self, rollover: Rollover) -> Self {
+        let value = match rollover {
+            Rollover::Zero => "0",
+            Rollover::One => "1",
+        };
+        self.option(negotiate::ROLLOVER, value)
+    }
+
+    /// Sets how long to wait for the server before sending the last packet
+    /// again, which is 3 seconds by default.
+    ///
+    /// Unlike `timeout`, this is not communicated to the server. A
+    /// negotiated timeout takes precedence once the server accepts it.
+    pub fn retransmit_timeout(mut self, timeout: Duration) -> Self {
+        self.data.settings.timeout = cmp::max(timeout, MIN_TIMEOUT);
+        self
+    }
+
+    /// Lets the timeout adapt to the measured round trip time to the
+    /// server, staying between `min` and `max`.
+    ///
+    /// The timeout starts out as the one set by `retransmit_timeout` and
+    /// doubles each time it elapses (RFC 6298). A negotiated timeout keeps
+    /// the timeout fixed instead.
+    pub fn adaptive_timeout(mut self, min: Duration, max: Duration) -> Self {
+        let min = cmp::max(min, MIN_TIMEOUT);
+        self.data.settings.timeout_bounds = Some((min, cmp::max(min, max)));
+        self
+    }
+
+    /// Sets how many times the last packet is sent again before the
+    /// transfer fails with `ErrorKind::TimedOut`, which is 5 by default.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.data.settings.max_retries = retries;
+        self
+    }
+
     /// Constructs the client.
     pub fn build(self) -> Client {
         Client {
             server: self.data.server,
             socket: self.data.socket,
+            options: self.data.options,
+            settings: self.data.settings,
         }
     }
 
@@ -78,6 +176,8 @@ impl Builder<ConnectTo> {
         let data = ConnectTo {
             server: self.data.server.clone(),
             socket: new_sock_builder.data.socket,
+            options: self.data.options.clone(),
+            settings: self.data.settings,
         };
         Ok(Builder { data })
     }
@@ -86,39 +186,139 @@ impl Builder<ConnectTo> {
 impl Client {
     /// Retrieves a file from the remote server.
     pub fn get<S: AsRef<str>, W: Write>(self, file: S, mode: Mode, writer: W) -> Result<W> {
-        let rrq = Packet::rrq(file, mode);
-        let _ = self
-            .socket
-            .send_to(&rrq.into_bytes()[..], &self.server[..])?;
+        self.get_with(file, mode, |_| writer)
+    }
+
+    /// Retrieves a file from the remote server, creating the writer once the
+    /// transfer has been accepted.
+    ///
+    /// `make_writer` is given the size of the file if the server reported
+    /// it (see `Builder::transfer_size`), which is useful for preallocating
+    /// memory or reporting progress. In `Mode::NetAscii`, this is the size
+    /// on the wire, which may be larger than the translated file.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `file` or one of the options
+    /// contains a NUL byte, which cannot be sent.
+    pub fn get_with<S, W, F>(self, file: S, mode: Mode, make_writer: F) -> Result<W>
+    where
+        S: AsRef<str>,
+        W: Write,
+        F: FnOnce(Option<u64>) -> W,
+    {
+        let rrq = Packet::rrq_with_options(file, mode, self.options.clone());
 
         let mut buf = [0; MAX_PACKET_SIZE];
-        let (_, server) = self.socket.peek_from(&mut buf)?;
-        self.socket.connect(server)?;
+        let (nbytes, peer) = self.request(rrq.encode()?, &mut buf)?;
+
+        // The server has accepted some of our options, so we must
+        // acknowledge its OACK with block 0 before it starts sending data.
+        // Otherwise, the first `Data` packet is left for the `Connection`.
+        let oack = Packet::<Oack>::from_bytes(&buf[..nbytes]).ok();
+        let settings = match oack {
+            Some(ref oack) => {
+                let _ = self.socket.recv_from(&mut buf)?;
+                self.accept_oack(peer, &oack.body)?
+            }
+            None => self.settings,
+        };
+
+        let writer = make_writer(settings.transfer_size);
+        let mut conn = Connection::new(self.socket, peer, settings)?;
+        if oack.is_some() {
+            conn.expect_repeated_oack();
+            conn.send(Packet::ack(Block::new(0)).into_bytes())?;
+        }
 
-        let conn = Connection::new(self.socket);
-        conn.get(writer)
+        match mode {
+            Mode::NetAscii => conn.get(NetAsciiWriter::new(writer))?.finish(),
+            _ => conn.get(writer),
+        }
     }
 
     /// Stores a file on the remote server.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `file` or one of the options
+    /// contains a NUL byte, which cannot be sent.
     pub fn put<S: AsRef<str>, R: Read>(self, file: S, mode: Mode, reader: R) -> Result<()> {
-        let wrq = Packet::wrq(file, mode);
-        let _ = self
-            .socket
-            .send_to(&wrq.into_bytes()[..], &self.server[..])?;
+        let wrq = Packet::wrq_with_options(file, mode, self.options.clone());
 
         let mut buf = [0; MAX_PACKET_SIZE];
-        let (nbytes, server) = self.socket.recv_from(&mut buf)?;
-        self.socket.connect(server)?;
+        let (nbytes, peer) = self.request(wrq.encode()?, &mut buf)?;
+        let _ = self.socket.recv_from(&mut buf)?;
 
-        let _ = match Packet::<Ack>::from_bytes(&buf[..nbytes]) {
-            Ok(a) => a,
-            Err(e) => {
-                let error: Packet<Error> = e.into();
+        // An OACK takes the place of the ACK for block 0.
+        let oack = Packet::<Oack>::from_bytes(&buf[..nbytes]).ok();
+        let settings = if let Some(ref oack) = oack {
+            self.accept_oack(peer, &oack.body)?
+        } else {
+            let ack: Packet<Ack> = Peer::new(&self.socket, peer).expect_packet(&buf[..nbytes])?;
+            if ack.body.block != Block::new(0) {
+                let error = Packet::error(
+                    Code::IllegalOperation,
+                    format!("expected ACK for block 0 but got {:?}", ack.body.block),
+                );
+                let _ = self.socket.send_to(&error.clone().into_bytes()[..], peer);
                 return Err(io::Error::from(error));
             }
+            self.settings
         };
 
-        let conn = Connection::new(self.socket);
-        conn.put(reader)
+        let mut conn = Connection::new(self.socket, peer, settings)?;
+        if oack.is_some() {
+            conn.expect_repeated_oack();
+        }
+        match mode {
+            Mode::NetAscii => conn.put(NetAsciiReader::new(reader)),
+            _ => conn.put(reader),
+        }
+    }
+
+    /// Sends a request to the server and peeks at its first response,
+    /// sending the request again each time the timeout elapses.
+    ///
+    /// Returns the size of the response along with the Transfer ID the
+    /// server chose for this transfer. Packets from hosts other than the
+    /// server are rejected along the way.
+    fn request(&self, rq: Vec<u8>, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        self.socket.set_read_timeout(Some(self.settings.timeout))?;
+
+        let mut retries = 0;
+        let _ = self.socket.send_to(&rq[..], &self.server[..])?;
+
+        loop {
+            match self.socket.peek_from(buf) {
+                Ok((nbytes, src)) if self.is_server(src) => return Ok((nbytes, src)),
+                Ok((_, src)) => {
+                    let _ = self.socket.recv_from(buf)?;
+                    reject_unknown_tid(&self.socket, src);
+                }
+                Err(err) if is_timeout(&err) && retries < self.settings.max_retries => {
+                    retries += 1;
+                    let _ = self.socket.send_to(&rq[..], &self.server[..])?;
+                }
+                Err(err) if is_timeout(&err) => return Err(timed_out(retries)),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Returns `true` if `src` belongs to the server the request was sent
+    /// to. The server answers from a port of its choosing, so only the
+    /// address is compared.
+    fn is_server(&self, src: SocketAddr) -> bool {
+        self.server
+            .iter()
+            .any(|server| server.ip().is_unspecified() || server.ip() == src.ip())
+    }
+
+    /// Checks the options acknowledged by the server against the ones we
+    /// asked for. If they do not agree, the transfer is terminated as
+    /// RFC 2347 requires.
+    fn accept_oack(&self, peer: SocketAddr, oack: &Oack) -> Result<Settings> {
+        negotiate::apply(&self.options, &oack.options, self.settings).map_err(|message| {
+            let error = Packet::error(Code::OptionNegotiation, message);
+            let _ = self.socket.send_to(&error.clone().into_bytes()[..], peer);
+            io::Error::from(error)
+        })
     }
 }
@@ -1,111 +1,484 @@
-use std::net::UdpSocket;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::net::{SocketAddr, UdpSocket};
 use std::{
     io::{self, Read, Result, Write},
     time::Duration,
 };
 
-use crate::bytes::IntoBytes;
-use crate::packet::expect::ExpectPacket;
+use crate::bytes::{FromBytes, IntoBytes};
+use crate::packet::expect::{ExpectPacket, Peer};
 use crate::packet::*;
-
-/*
- * TODO: Probably add support for timeouts and retransmissions */
+use crate::rto::Rto;
 
 pub const MIN_PORT_NUMBER: u16 = 1024;
 
+/// How long to wait for the peer unless a timeout has been negotiated.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The shortest timeout that may be configured.
+pub const MIN_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// How many times a packet is sent again before the peer is given up on.
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// The size of a `Data` packet minus its payload. (2 byte opcode + 2 byte block ID)
+const DATA_HEADER_SIZE: usize = MAX_PACKET_SIZE - MAX_PAYLOAD_SIZE;
+
+/// Parameters for a single transfer, as agreed upon through option
+/// negotiation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Settings {
+    /// The number of bytes carried by each `Data` packet, except the last.
+    pub block_size: usize,
+
+    /// The size of the file being transferred, if it is known.
+    pub transfer_size: Option<u64>,
+
+    /// How long to wait for the peer before sending the last packet again.
+    pub timeout: Duration,
+
+    /// The bounds within which the timeout adapts to the measured round
+    /// trip time, starting from `timeout`. Without them, the timeout is
+    /// fixed.
+    pub timeout_bounds: Option<(Duration, Duration)>,
+
+    /// How many times the last packet is sent again before giving up.
+    pub max_retries: u32,
+
+    /// The number of `Data` packets sent before waiting for an `Ack`.
+    pub window_size: u16,
+
+    /// What the block number wraps around to after block 65535.
+    pub rollover: Rollover,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            block_size: MAX_PAYLOAD_SIZE,
+            transfer_size: None,
+            timeout: DEFAULT_TIMEOUT,
+            timeout_bounds: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            window_size: 1,
+            rollover: Rollover::default(),
+        }
+    }
+}
+
 pub struct Connection {
     socket: UdpSocket,
+
+    /// The Transfer ID of the peer. Packets from anywhere else are
+    /// rejected.
+    peer: SocketAddr,
+
+    settings: Settings,
+
+    /// The last packet sent to the peer, which is sent again if the peer
+    /// does not answer in time.
+    last_sent: Vec<u8>,
+
+    /// How long to wait for the peer, which adapts to how quickly it has
+    /// answered so far.
+    rto: Cell<Rto>,
+
+    /// Whether the peer acknowledged our options with an `Oack`, which it
+    /// sends again if our answer to it is lost.
+    oack: bool,
 }
 
 impl Connection {
-    pub fn new(socket: UdpSocket) -> Self {
-        Self { socket }
+    pub fn new(socket: UdpSocket, peer: SocketAddr, settings: Settings) -> Result<Self> {
+        let rto = Rto::new(&settings);
+        socket.set_read_timeout(Some(rto.timeout()))?;
+
+        Ok(Self {
+            socket,
+            peer,
+            settings,
+            last_sent: Vec::new(),
+            rto: Cell::new(rto),
+            oack: false,
+        })
+    }
+
+    /// Takes any `Oack` that the peer sends during the transfer to be a
+    /// copy of the one it already sent, rather than an error.
+    pub fn expect_repeated_oack(&mut self) {
+        self.oack = true;
     }
 
-    pub fn get<W: Write>(self, mut writer: W) -> Result<W> {
+    /// Whether `bytes` is a copy of the `Oack` that the peer already sent.
+    fn is_repeated_oack(&self, bytes: &[u8]) -> bool {
+        self.oack && Packet::<Oack>::from_bytes(bytes).is_ok()
+    }
+
+    /// Sends a packet to the peer, remembering it in case it has to be
+    /// sent again.
+    pub fn send(&mut self, bytes: Vec<u8>) -> Result<()> {
+        let _ = self.socket.send_to(&bytes[..], self.peer)?;
+        self.last_sent = bytes;
+        self.update_rto(Rto::start);
+        Ok(())
+    }
+
+    /// Sends an `Oack` to the peer and waits for it to be acknowledged with
+    /// block 0, which must happen before the first `Data` packet of a read
+    /// request can be sent.
+    pub fn handshake(&mut self, oack: Packet<Oack>) -> Result<()> {
+        self.send(oack.into_bytes())?;
+
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let bytes_recvd = self.recv(&mut buf, || self.resend())?;
+
+        let ack: Packet<Ack> =
+            Peer::new(&self.socket, self.peer).expect_packet(&buf[..bytes_recvd])?;
+        self.update_rto(Rto::stop);
+        if ack.body.block != Block::new(0) {
+            let error = Packet::error(
+                Code::IllegalOperation,
+                format!("expected ACK for block 0 but got {:?}", ack.body.block),
+            );
+            let _ = self
+                .socket
+                .send_to(&error.clone().into_bytes()[..], self.peer);
+            return Err(io::Error::from(error));
+        }
+
+        Ok(())
+    }
+
+    pub fn get<W: Write>(self, writer: W) -> Result<W> {
+        self.get_then(writer, Ok)
+    }
+
+    /// Like `get`, but hands the writer to `finish` once the last block has
+    /// been written. The last block is only acknowledged if `finish`
+    /// succeeds, so the sender learns when it does not.
+    pub fn get_then<W, T, F>(mut self, mut writer: W, finish: F) -> Result<T>
+    where
+        W: Write,
+        F: FnOnce(W) -> Result<T>,
+    {
+        let block_size = self.settings.block_size;
+        let window_size = self.settings.window_size as usize;
+
+        // One byte of slack so that oversized packets can be told apart
+        // from full ones rather than being silently truncated.
+        let mut buf = vec![0; DATA_HEADER_SIZE + block_size + 1];
+
+        let rollover = self.settings.rollover;
+        let block = |seq| Block::from_sequence(seq, rollover);
+
+        // Blocks are counted from the start of the transfer so that block
+        // numbers can wrap around on the wire.
+        let mut expected: u64 = 1;
+        let mut unacked = 0;
+        let mut gap_reported = false;
+
         loop {
-            let mut buf = [0; MAX_PACKET_SIZE];
-            let bytes_recvd = self.socket.recv(&mut buf)?;
+            let bytes_recvd = self.recv(&mut buf, || self.resend())?;
 
-            let data: Packet<Data> = self.socket.expect_packet(&buf[..bytes_recvd])?;
+            // Our ACK for block 0 was lost. Until the first block arrives,
+            // it is still the last packet we sent.
+            if self.is_repeated_oack(&buf[..bytes_recvd]) {
+                if expected == 1 {
+                    self.resend()?;
+                }
+                continue;
+            }
 
-            if let Err(err) = writer.write_all(&data.body.data[..]) {
+            let data: Packet<Data> =
+                Peer::new(&self.socket, self.peer).expect_packet(&buf[..bytes_recvd])?;
+
+            let offset = data.body.block.offset_from(block(expected), rollover);
+            if offset != 0 {
+                if expected > 1 && data.body.block == block(expected - 1) {
+                    // The sender never saw our ACK for this block and sent it
+                    // again. Acknowledge it again, but do not write it twice.
+                    self.send(Packet::ack(data.body.block).into_bytes())?;
+                    unacked = 0;
+                } else if offset < window_size as u32 {
+                    // A block within the window went missing, so tell the
+                    // sender to go back to the first block we do not have.
+                    // Any blocks that follow it in the window are discarded.
+                    if !gap_reported {
+                        self.send(Packet::ack(block(expected - 1)).into_bytes())?;
+                        gap_reported = true;
+                        unacked = 0;
+                    }
+                } else if offset < 0x8000 {
+                    let error = Packet::error(
+                        Code::IllegalOperation,
+                        format!(
+                            "expected {:?} but got {:?}",
+                            block(expected),
+                            data.body.block
+                        ),
+                    );
+                    let _ = self
+                        .socket
+                        .send_to(&error.clone().into_bytes()[..], self.peer);
+                    return Err(io::Error::from(error));
+                }
+
+                // Anything else is a stale copy of a block that was already
+                // written and acknowledged.
+                continue;
+            }
+
+            if data.body.data.len() > block_size {
+                let error = Packet::error(
+                    Code::IllegalOperation,
+                    format!(
+                        "{:?} carries more than {} bytes",
+                        data.body.block, block_size
+                    ),
+                );
                 let _ = self
                     .socket
-                    .send(&Packet::error(err.kind().into(), format!("{}", err)).into_bytes()[..]);
+                    .send_to(&error.clone().into_bytes()[..], self.peer);
+                return Err(io::Error::from(error));
+            }
+
+            self.update_rto(Rto::stop);
+
+            if let Err(err) = writer.write_all(&data.body.data[..]) {
+                let error = Packet::error(err.kind().into(), format!("{}", err));
+                let _ = self.socket.send_to(&error.into_bytes()[..], self.peer);
                 return Err(err);
             }
 
+            expected += 1;
+            unacked += 1;
+            gap_reported = false;
+
             let payload_size = data.body.data.len();
             let ack = Packet::<Ack>::from(data);
 
-            if payload_size < MAX_PAYLOAD_SIZE {
-                self.socket.set_read_timeout(Some(Duration::new(3, 0)))?;
-                loop {
-                    let _ = self.socket.send(&ack.clone().into_bytes()[..])?;
-
-                    if let Err(err) = self.socket.recv(&mut buf) {
-                        match err.kind() {
-                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
-                                break;
-                            }
-                            _ => return Err(err),
-                        }
-                    };
+            if payload_size < block_size {
+                let finished = match finish(writer) {
+                    Ok(finished) => finished,
+                    Err(err) => {
+                        let error = Packet::error(err.kind().into(), format!("{}", err));
+                        let _ = self.socket.send_to(&error.into_bytes()[..], self.peer);
+                        return Err(err);
+                    }
+                };
+
+                // Our final ACK may get lost, in which case the sender
+                // retransmits its final block. Linger long enough to
+                // acknowledge it again.
+                self.send(ack.into_bytes())?;
+                self.socket
+                    .set_read_timeout(Some(self.rto.get().timeout()))?;
+                for _ in 0..self.settings.max_retries {
+                    match self.socket.recv_from(&mut buf) {
+                        Ok((_, src)) if src == self.peer => self.resend()?,
+                        Ok((_, src)) => reject_unknown_tid(&self.socket, src),
+                        Err(err) if is_timeout(&err) => break,
+                        Err(err) => return Err(err),
+                    }
                 }
-                break;
+                return Ok(finished);
             }
-            let _ = self.socket.send(&ack.into_bytes()[..])?;
-        }
 
-        Ok(writer)
+            // Only the last block of each window is acknowledged.
+            if unacked == window_size {
+                self.send(ack.into_bytes())?;
+                unacked = 0;
+            }
+        }
     }
 
     pub fn put<R: Read>(self, mut reader: R) -> Result<()> {
-        let mut current_block = 1;
+        let block_size = self.settings.block_size;
+        let window_size = self.settings.window_size as usize;
+
+        // The blocks that have been read but not yet acknowledged, oldest
+        // first.
+        let mut window: VecDeque<Packet<Data>> = VecDeque::with_capacity(window_size);
+
+        let rollover = self.settings.rollover;
+        let block = |seq| Block::from_sequence(seq, rollover);
+
+        let mut next_block: u64 = 1;
+        let mut read_all = false;
+
+        // Whether the window has changed since it was last sent.
+        let mut stale = true;
 
         loop {
-            let mut buf = [0; MAX_PAYLOAD_SIZE];
+            while window.len() < window_size && !read_all {
+                let mut buf = vec![0; block_size];
+
+                let bytes_read = match read_block(&mut reader, &mut buf) {
+                    Ok(bytes_read) => bytes_read,
+                    Err(err) => {
+                        let error = Packet::error(err.kind().into(), format!("{}", err));
+                        let _ = self.socket.send_to(&error.into_bytes()[..], self.peer);
+                        return Err(err);
+                    }
+                };
+
+                window.push_back(Packet::data(block(next_block), &buf[..bytes_read]));
+                next_block += 1;
+                read_all = bytes_read < block_size;
+            }
 
-            let bytes_read = match reader.read(&mut buf) {
-                Ok(bytes_read) => bytes_read,
-                Err(err) => {
-                    let _ = self.socket.send(
-                        &Packet::error(err.kind().into(), format!("{}", err)).into_bytes()[..],
-                    );
-                    return Err(err);
+            // Sending the whole window again is also how lost blocks are
+            // recovered when the peer stops answering.
+            let send_window = || -> Result<()> {
+                for data in window.iter() {
+                    let _ = self
+                        .socket
+                        .send_to(&data.clone().into_bytes()[..], self.peer)?;
                 }
+                Ok(())
             };
-
-            let data = Packet::data(Block::new(current_block), buf[..bytes_read].to_vec());
-
-            let _ = self.socket.send(&data.into_bytes()[..])?;
+            if stale {
+                send_window()?;
+                self.update_rto(Rto::start);
+            }
 
             let mut buf = [0; MAX_PACKET_SIZE];
-            let bytes_recvd = self.socket.recv(&mut buf)?;
+            let bytes_recvd = self.recv(&mut buf, send_window)?;
 
-            let ack: Packet<Ack> = self.socket.expect_packet(&buf[..bytes_recvd])?;
+            // Our first block was lost. It is sent again along with the rest
+            // of the window once the timeout elapses.
+            if self.is_repeated_oack(&buf[..bytes_recvd]) {
+                stale = false;
+                continue;
+            }
 
-            if Block::new(current_block) != ack.body.block {
-                let error = Packet::error(
-                    Code::IllegalOperation,
-                    format!(
-                        "expected ACK for {:?} but got ACK for {:?}",
-                        current_block, ack.body.block
-                    ),
-                );
-                self.socket.send(&error.clone().into_bytes()[..])?;
-                return Err(io::Error::from(error));
+            let ack: Packet<Ack> =
+                Peer::new(&self.socket, self.peer).expect_packet(&buf[..bytes_recvd])?;
+
+            let base = next_block - window.len() as u64;
+            let offset = ack.body.block.offset_from(block(base), rollover);
+            if offset as usize >= window.len() {
+                if offset < 0x8000 {
+                    let error = Packet::error(
+                        Code::IllegalOperation,
+                        format!(
+                            "expected ACK for {:?} but got ACK for {:?}",
+                            block(base),
+                            ack.body.block
+                        ),
+                    );
+                    self.socket
+                        .send_to(&error.clone().into_bytes()[..], self.peer)?;
+                    return Err(io::Error::from(error));
+                }
+
+                // A duplicate ACK for a block that was already acknowledged.
+                // Answering it with the window again is what causes the
+                // Sorcerer's Apprentice Syndrome (RFC 1123), so lost blocks
+                // are only sent again once the timeout elapses.
+                stale = false;
+                continue;
             }
-            current_block += 1;
 
-            if bytes_read < MAX_PAYLOAD_SIZE {
+            // Slide the window past the acknowledged blocks. Any that are left
+            // were lost and are sent again along with the next window.
+            window.drain(..=offset as usize);
+            self.update_rto(Rto::stop);
+            stale = true;
+
+            if read_all && window.is_empty() {
                 break;
             }
         }
 
         Ok(())
     }
+
+    fn update_rto<F: FnOnce(&mut Rto)>(&self, f: F) {
+        let mut rto = self.rto.get();
+        f(&mut rto);
+        self.rto.set(rto);
+    }
+
+    /// Sends the last packet to the peer again.
+    fn resend(&self) -> Result<()> {
+        if !self.last_sent.is_empty() {
+            let _ = self.socket.send_to(&self.last_sent[..], self.peer)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the next packet from the peer, calling `resend` each time
+    /// the timeout elapses.
+    ///
+    /// Gives up with `ErrorKind::TimedOut` once the maximum number of
+    /// retries has been reached.
+    fn recv<F: FnMut() -> Result<()>>(&self, buf: &mut [u8], mut resend: F) -> Result<usize> {
+        let mut retries = 0;
+
+        loop {
+            self.socket
+                .set_read_timeout(Some(self.rto.get().timeout()))?;
+
+            match self.socket.recv_from(buf) {
+                Ok((bytes_recvd, src)) if src == self.peer => return Ok(bytes_recvd),
+                Ok((_, src)) => reject_unknown_tid(&self.socket, src),
+                Err(err) if is_timeout(&err) && retries < self.settings.max_retries => {
+                    retries += 1;
+                    self.update_rto(Rto::backoff);
+                    resend()?;
+                }
+                Err(err) if is_timeout(&err) => return Err(timed_out(retries)),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Answers a packet from a Transfer ID that is not part of the transfer
+/// with an error, as RFC 1350 requires. The transfer itself carries on.
+pub fn reject_unknown_tid(socket: &UdpSocket, src: SocketAddr) {
+    let error = Packet::error(Code::UnknownTid, Code::UnknownTid.as_str());
+    let _ = socket.send_to(&error.into_bytes()[..], src);
+}
+
+/// Returns `true` if `err` was caused by a read timeout elapsing.
+///
+/// Depending on the platform, this is reported as either `WouldBlock` or
+/// `TimedOut`.
+pub fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// The error reported when the peer has stopped answering.
+pub fn timed_out(retries: u32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("peer did not respond after {} retransmissions", retries),
+    )
+}
+
+/// Fills `buf` from `reader`, stopping early only at the end of the input.
+///
+/// A single `read` may return fewer bytes than requested, which would be
+/// mistaken for the final block of the transfer.
+fn read_block<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(filled)
 }
 
 #[cfg(test)]
@@ -137,7 +510,8 @@ mod tests {
         client_sock.connect(("localhost", server_port)).unwrap();
 
         // Create a connection struct for our client
-        let client_conn = Connection::new(client_sock);
+        let server_addr = server_sock.local_addr().unwrap();
+        let client_conn = Connection::new(client_sock, server_addr, Settings::default()).unwrap();
 
         // Send an (hopefully) invalid packet
         server_sock
@@ -155,7 +529,7 @@ mod tests {
         let mut buf = [0; MAX_PACKET_SIZE];
         let rcvd = loop {
             let (rcvd, _) = server_sock.recv_from(&mut buf).unwrap();
-            if let Ok(_) = Packet::<Data>::from_bytes(&buf[..rcvd]) {
+            if Packet::<Data>::from_bytes(&buf[..rcvd]).is_ok() {
                 continue;
             }
             break rcvd;
@@ -175,4 +549,422 @@ mod tests {
     fn test_put_sends_invalid_packet_error() {
         test_blank_sends_invalid_packet_error(|conn| conn.put(&b"wowzers"[..]))
     }
+
+    fn socket_pair() -> (UdpSocket, UdpSocket) {
+        let ours = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let theirs = UdpSocket::bind("127.0.0.1:0").unwrap();
+        theirs.connect(ours.local_addr().unwrap()).unwrap();
+        (ours, theirs)
+    }
+
+    fn recv_data(socket: &UdpSocket) -> Packet<Data> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let nbytes = socket.recv(&mut buf).unwrap();
+        Packet::<Data>::from_bytes(&buf[..nbytes]).unwrap()
+    }
+
+    fn recv_ack(socket: &UdpSocket) -> Packet<Ack> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let nbytes = socket.recv(&mut buf).unwrap();
+        Packet::<Ack>::from_bytes(&buf[..nbytes]).unwrap()
+    }
+
+    #[test]
+    fn test_put_goes_back_to_unacknowledged_block() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            window_size: 4,
+            ..Settings::default()
+        };
+
+        let sender = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.put(&[7u8; 40][..])
+        });
+
+        for block in 1..=4 {
+            let data = recv_data(&theirs);
+            assert_eq!(data.body.block, Block::new(block));
+        }
+
+        // Pretend that block 3 was lost.
+        theirs
+            .send(&Packet::ack(Block::new(2)).into_bytes()[..])
+            .unwrap();
+
+        for block in 3..=6 {
+            let data = recv_data(&theirs);
+            assert_eq!(data.body.block, Block::new(block));
+        }
+
+        theirs
+            .send(&Packet::ack(Block::new(6)).into_bytes()[..])
+            .unwrap();
+        sender.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_get_acknowledges_windows() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            window_size: 2,
+            timeout: Duration::from_millis(100),
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get(Vec::new())
+        });
+
+        let send = |block, payload: &[u8]| {
+            theirs
+                .send(&Packet::data(Block::new(block), payload).into_bytes()[..])
+                .unwrap();
+        };
+
+        send(1, b"aaaaaaaa");
+        send(2, b"bbbbbbbb");
+        let ack = recv_ack(&theirs);
+        assert_eq!(ack.body.block, Block::new(2));
+
+        // Block 3 goes missing, so the receiver asks for it again.
+        send(4, b"dddddddd");
+        let ack = recv_ack(&theirs);
+        assert_eq!(ack.body.block, Block::new(2));
+
+        send(3, b"cccccccc");
+        send(4, b"dddddddd");
+        let ack = recv_ack(&theirs);
+        assert_eq!(ack.body.block, Block::new(4));
+
+        send(5, b"e");
+        let ack = recv_ack(&theirs);
+        assert_eq!(ack.body.block, Block::new(5));
+
+        let actual = receiver.join().unwrap().unwrap();
+        assert_eq!(&actual[..], b"aaaaaaaabbbbbbbbccccccccdddddddde");
+    }
+
+    #[test]
+    fn test_put_retransmits_on_timeout() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            timeout: Duration::from_millis(50),
+            ..Settings::default()
+        };
+
+        let sender = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.put(&b"hello"[..])
+        });
+
+        // Pretend the first copy of the block never arrived.
+        let data = recv_data(&theirs);
+        assert_eq!(data.body.block, Block::new(1));
+        let data = recv_data(&theirs);
+        assert_eq!(data.body.block, Block::new(1));
+        assert_eq!(&data.body.data[..], b"hello");
+
+        theirs
+            .send(&Packet::ack(Block::new(1)).into_bytes()[..])
+            .unwrap();
+        sender.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_get_retransmits_last_ack_on_timeout() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            timeout: Duration::from_millis(50),
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get(Vec::new())
+        });
+
+        theirs
+            .send(&Packet::data(Block::new(1), b"aaaaaaaa").into_bytes()[..])
+            .unwrap();
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(1));
+
+        // Pretend the ACK never arrived.
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(1));
+
+        theirs
+            .send(&Packet::data(Block::new(2), b"b").into_bytes()[..])
+            .unwrap();
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(2));
+
+        let actual = receiver.join().unwrap().unwrap();
+        assert_eq!(&actual[..], b"aaaaaaaab");
+    }
+
+    #[test]
+    fn test_gives_up_after_max_retries() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            timeout: Duration::from_millis(20),
+            max_retries: 2,
+            ..Settings::default()
+        };
+
+        let conn = Connection::new(ours, peer, settings).unwrap();
+        let err = conn.put(&b"hello"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // The block was sent once and then retransmitted twice.
+        theirs
+            .set_read_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+        for _ in 0..3 {
+            assert_eq!(recv_data(&theirs).body.block, Block::new(1));
+        }
+        let mut buf = [0; MAX_PACKET_SIZE];
+        assert!(theirs.recv(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_get_acknowledges_duplicates_without_writing_them() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            timeout: Duration::from_millis(100),
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get(Vec::new())
+        });
+
+        let send = |block, payload: &[u8]| {
+            theirs
+                .send(&Packet::data(Block::new(block), payload).into_bytes()[..])
+                .unwrap();
+        };
+
+        send(1, b"aaaaaaaa");
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(1));
+        send(2, b"bbbbbbbb");
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(2));
+
+        // Our ACK for block 2 was "lost", so it is sent again.
+        send(2, b"bbbbbbbb");
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(2));
+
+        // A stale copy of block 1 is ignored.
+        send(1, b"aaaaaaaa");
+        send(3, b"c");
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(3));
+
+        let actual = receiver.join().unwrap().unwrap();
+        assert_eq!(&actual[..], b"aaaaaaaabbbbbbbbc");
+    }
+
+    #[test]
+    fn test_get_rejects_blocks_that_skip_ahead() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get(Vec::new())
+        });
+
+        theirs
+            .send(&Packet::data(Block::new(1), b"aaaaaaaa").into_bytes()[..])
+            .unwrap();
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(1));
+
+        theirs
+            .send(&Packet::data(Block::new(3), b"cccccccc").into_bytes()[..])
+            .unwrap();
+
+        let err = receiver.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let nbytes = theirs.recv(&mut buf).unwrap();
+        let error = Packet::<Error>::from_bytes(&buf[..nbytes]).unwrap();
+        assert_eq!(error.body.code, Code::IllegalOperation);
+    }
+
+    #[test]
+    fn test_put_ignores_duplicate_acks() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            timeout: Duration::from_millis(500),
+            ..Settings::default()
+        };
+
+        let sender = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.put(&b"aaaaaaaabbbbbbbbc"[..])
+        });
+
+        let ack = |block| {
+            theirs
+                .send(&Packet::ack(Block::new(block)).into_bytes()[..])
+                .unwrap();
+        };
+
+        assert_eq!(recv_data(&theirs).body.block, Block::new(1));
+        ack(1);
+        assert_eq!(recv_data(&theirs).body.block, Block::new(2));
+
+        // A delayed copy of the first ACK must not trigger a retransmission.
+        ack(1);
+        theirs
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let mut buf = [0; MAX_PACKET_SIZE];
+        assert!(theirs.recv(&mut buf).is_err());
+        theirs.set_read_timeout(None).unwrap();
+
+        ack(2);
+        assert_eq!(recv_data(&theirs).body.block, Block::new(3));
+        ack(3);
+
+        sender.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_put_rejects_ack_for_unsent_block() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+
+        let sender = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, Settings::default()).unwrap();
+            conn.put(&b"hello"[..])
+        });
+
+        assert_eq!(recv_data(&theirs).body.block, Block::new(1));
+        theirs
+            .send(&Packet::ack(Block::new(7)).into_bytes()[..])
+            .unwrap();
+
+        let err = sender.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_rejects_packets_from_unknown_tid() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stranger.connect(ours.local_addr().unwrap()).unwrap();
+        let settings = Settings {
+            block_size: 8,
+            timeout: Duration::from_millis(100),
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get(Vec::new())
+        });
+
+        theirs
+            .send(&Packet::data(Block::new(1), b"aaaaaaaa").into_bytes()[..])
+            .unwrap();
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(1));
+
+        stranger
+            .send(&Packet::data(Block::new(2), b"evil").into_bytes()[..])
+            .unwrap();
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let nbytes = stranger.recv(&mut buf).unwrap();
+        let error = Packet::<Error>::from_bytes(&buf[..nbytes]).unwrap();
+        assert_eq!(error.body.code, Code::UnknownTid);
+
+        theirs
+            .send(&Packet::data(Block::new(2), b"b").into_bytes()[..])
+            .unwrap();
+        assert_eq!(recv_ack(&theirs).body.block, Block::new(2));
+
+        let actual = receiver.join().unwrap().unwrap();
+        assert_eq!(&actual[..], b"aaaaaaaab");
+    }
+
+    #[test]
+    fn test_put_adapts_timeout_to_round_trip_time() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            timeout: Duration::from_secs(5),
+            timeout_bounds: Some((Duration::from_millis(20), Duration::from_secs(5))),
+            ..Settings::default()
+        };
+
+        let sender = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.put(&b"aaaaaaaab"[..])
+        });
+
+        assert_eq!(recv_data(&theirs).body.block, Block::new(1));
+        theirs
+            .send(&Packet::ack(Block::new(1)).into_bytes()[..])
+            .unwrap();
+
+        // The first ACK came back quickly, so a lost block is sent again
+        // long before the initial timeout.
+        let sent = std::time::Instant::now();
+        assert_eq!(recv_data(&theirs).body.block, Block::new(2));
+        assert_eq!(recv_data(&theirs).body.block, Block::new(2));
+        assert!(sent.elapsed() < Duration::from_secs(1));
+
+        theirs
+            .send(&Packet::ack(Block::new(2)).into_bytes()[..])
+            .unwrap();
+        sender.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_get_then_withholds_final_ack_when_finish_fails() {
+        let (ours, theirs) = socket_pair();
+        let peer = theirs.local_addr().unwrap();
+        let settings = Settings {
+            block_size: 8,
+            ..Settings::default()
+        };
+
+        let receiver = std::thread::spawn(move || {
+            let conn = Connection::new(ours, peer, settings).unwrap();
+            conn.get_then(Vec::new(), |data: Vec<u8>| -> Result<()> {
+                assert_eq!(&data[..], b"abc");
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, "read only"))
+            })
+        });
+
+        theirs
+            .send(&Packet::data(Block::new(1), &b"abc"[..]).into_bytes()[..])
+            .unwrap();
+
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let nbytes = theirs.recv(&mut buf).unwrap();
+        let error = Packet::<Error>::from_bytes(&buf[..nbytes]).unwrap();
+        assert_eq!(error.body.code, Code::AccessViolation);
+
+        let err = receiver.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
 }
@@ -10,6 +10,14 @@
 //! For more information, please see [THE TFTP PROTOCOL (REVISION 2)](
 //! https://tools.ietf.org/html/rfc1350).
 //!
+//! The following extensions are supported as well:
+//!
+//! * Option negotiation (RFC 2347)
+//! * The `blksize` option (RFC 2348)
+//! * The `tsize` and `timeout` options (RFC 2349)
+//! * The `windowsize` option (RFC 7440)
+//! * The de-facto `rollover` option for transfers of more than 65535 blocks
+//!
 //! ## Try it out
 //!
 //! In one terminal window, start up the server:
@@ -42,8 +50,15 @@
 mod bytes;
 pub mod client;
 mod connection;
+mod negotiate;
+pub mod netascii;
 pub mod packet;
+mod rto;
 mod server;
+pub mod storage;
 
 pub use client::{Client, ConnectTo};
-pub use server::{Handler, Server};
+pub use server::{
+    BusyPolicy, Cidr, Content, Handler, OverwritePolicy, Provider, ReadRequest, RemapRule, Server,
+};
+pub use storage::{FileSystem, Storage, SymlinkPolicy};
@@ -0,0 +1,486 @@
+//! Negotiation of the option extensions (RFC 2347) that tune a transfer.
+
+use std::cmp;
+use std::time::Duration;
+
+use crate::connection::Settings;
+use crate::packet::*;
+
+/// The name of the block size option (RFC 2348).
+pub const BLKSIZE: &str = "blksize";
+
+/// The name of the transfer size option (RFC 2349).
+pub const TSIZE: &str = "tsize";
+
+/// The name of the timeout interval option (RFC 2349).
+pub const TIMEOUT: &str = "timeout";
+
+/// The name of the window size option (RFC 7440).
+pub const WINDOWSIZE: &str = "windowsize";
+
+/// The name of the block number rollover option, which is not covered by
+/// any RFC but is understood by many implementations.
+pub const ROLLOVER: &str = "rollover";
+
+/// The largest window a server agrees to unless configured otherwise.
+pub const DEFAULT_MAX_WINDOW_SIZE: u16 = 64;
+
+/// The upper bounds a server places on the options it accepts.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_block_size: usize,
+    pub max_upload_size: Option<u64>,
+    pub max_window_size: u16,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_block_size: MAX_BLOCK_SIZE,
+            max_upload_size: None,
+            max_window_size: DEFAULT_MAX_WINDOW_SIZE,
+        }
+    }
+}
+
+/// The kind of request that options are being negotiated for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Request {
+    /// A read request for a file of the given size, if it is known.
+    Read(Option<u64>),
+
+    /// A write request.
+    Write,
+}
+
+/// Picks the options from a request that the server supports, along with
+/// the values it agrees to use, and adjusts `settings` to match them.
+///
+/// Options that are not recognized or carry values that cannot be honored
+/// are left out, which tells the client that they will not be used.
+pub fn accept(
+    requested: &Options,
+    request: Request,
+    limits: &Limits,
+    mut settings: Settings,
+) -> (Options, Settings) {
+    let mut accepted = Options::new();
+
+    for (name, value) in requested.iter() {
+        if accepted.contains(name) {
+            continue;
+        }
+
+        if name.eq_ignore_ascii_case(BLKSIZE) {
+            let size = match value.parse::<usize>() {
+                Ok(size) if size >= MIN_BLOCK_SIZE => size,
+                _ => continue,
+            };
+
+            let size = cmp::min(size, cmp::min(limits.max_block_size, MAX_BLOCK_SIZE));
+            settings.block_size = size;
+            accepted.push(BLKSIZE, size.to_string());
+        } else if name.eq_ignore_ascii_case(TSIZE) {
+            let size = match (value.parse::<u64>(), request) {
+                (Ok(0), Request::Read(Some(size))) => size,
+                (Ok(size), Request::Write) => size,
+                _ => continue,
+            };
+
+            settings.transfer_size = Some(size);
+            accepted.push(TSIZE, size.to_string());
+        } else if name.eq_ignore_ascii_case(TIMEOUT) {
+            let secs = match value.parse::<u8>() {
+                Ok(secs) if secs >= 1 => secs,
+                _ => continue,
+            };
+
+            // Both peers have agreed on the timeout, so it no longer adapts.
+            settings.timeout = Duration::from_secs(secs.into());
+            settings.timeout_bounds = None;
+            accepted.push(TIMEOUT, secs.to_string());
+        } else if name.eq_ignore_ascii_case(WINDOWSIZE) {
+            let size = match value.parse::<u16>() {
+                Ok(size) if size >= 1 => size,
+                _ => continue,
+            };
+
+            let size = cmp::min(size, cmp::max(limits.max_window_size, 1));
+            settings.window_size = size;
+            accepted.push(WINDOWSIZE, size.to_string());
+        } else if name.eq_ignore_ascii_case(ROLLOVER) {
+            settings.rollover = match value {
+                "0" => Rollover::Zero,
+                "1" => Rollover::One,
+                _ => continue,
+            };
+            accepted.push(ROLLOVER, value);
+        }
+    }
+
+    (accepted, settings)
+}
+
+/// Checks the options acknowledged by a server against the ones that were
+/// requested and adjusts `settings` to match them.
+///
+/// On failure, the returned message explains why the acknowledgement was
+/// rejected.
+pub fn apply(
+    requested: &Options,
+    oack: &Options,
+    mut settings: Settings,
+) -> Result<Settings, String> {
+    for (name, value) in oack.iter() {
+        let asked = match requested.get(name) {
+            Some(asked) => asked,
+            None => return Err(format!("server acknowledged unrequested option {:?}", name)),
+        };
+
+        if name.eq_ignore_ascii_case(BLKSIZE) {
+            let size = value.parse::<usize>().ok();
+            let asked = asked.parse::<usize>().unwrap_or(MAX_BLOCK_SIZE);
+            settings.block_size = match size {
+                Some(size) if (MIN_BLOCK_SIZE..=asked).contains(&size) => size,
+                _ => {
+                    return Err(format!(
+                        "server acknowledged invalid block size {:?}",
+                        value
+                    ))
+                }
+            };
+        } else if name.eq_ignore_ascii_case(TSIZE) {
+            settings.transfer_size = match value.parse::<u64>() {
+                Ok(size) => Some(size),
+                Err(_) => {
+                    return Err(format!(
+                        "server acknowledged invalid transfer size {:?}",
+                        value
+                    ))
+                }
+            };
+        } else if name.eq_ignore_ascii_case(TIMEOUT) {
+            // The server must use the timeout exactly as it was requested.
+            settings.timeout = match value.parse::<u8>() {
+                Ok(secs) if secs >= 1 && value == asked => Duration::from_secs(secs.into()),
+                _ => return Err(format!("server acknowledged invalid timeout {:?}", value)),
+            };
+            settings.timeout_bounds = None;
+        } else if name.eq_ignore_ascii_case(WINDOWSIZE) {
+            let size = value.parse::<u16>().ok();
+            let asked = asked.parse::<u16>().unwrap_or(u16::MAX);
+            settings.window_size = match size {
+                Some(size) if (1..=asked).contains(&size) => size,
+                _ => {
+                    return Err(format!(
+                        "server acknowledged invalid window size {:?}",
+                        value
+                    ))
+                }
+            };
+        } else if name.eq_ignore_ascii_case(ROLLOVER) {
+            // The server must use the rollover exactly as it was requested.
+            settings.rollover = match value {
+                "0" if value == asked => Rollover::Zero,
+                "1" if value == asked => Rollover::One,
+                _ => return Err(format!("server acknowledged invalid rollover {:?}", value)),
+            };
+        }
+    }
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(pairs: &[(&str, &str)]) -> Options {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_accept_blksize() {
+        let limits = Limits::default();
+        let request = Request::Write;
+
+        let (accepted, settings) = accept(
+            &options(&[("blksize", "1428")]),
+            request,
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("blksize", "1428")]));
+        assert_eq!(settings.block_size, 1428);
+
+        let (accepted, settings) = accept(
+            &options(&[("BLKSIZE", "70000")]),
+            request,
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("blksize", "65464")]));
+        assert_eq!(settings.block_size, MAX_BLOCK_SIZE);
+
+        let (accepted, settings) = accept(
+            &options(&[("blksize", "4")]),
+            request,
+            &limits,
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+        assert_eq!(settings.block_size, MAX_PAYLOAD_SIZE);
+
+        let (accepted, _) = accept(
+            &options(&[("blksize", "lots")]),
+            request,
+            &limits,
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+
+        let limits = Limits {
+            max_block_size: 1024,
+            ..Limits::default()
+        };
+        let (accepted, settings) = accept(
+            &options(&[("blksize", "1428")]),
+            request,
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("blksize", "1024")]));
+        assert_eq!(settings.block_size, 1024);
+    }
+
+    #[test]
+    fn test_accept_tsize() {
+        let limits = Limits::default();
+        let requested = options(&[("tsize", "0")]);
+
+        let (accepted, settings) = accept(
+            &requested,
+            Request::Read(Some(1234)),
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("tsize", "1234")]));
+        assert_eq!(settings.transfer_size, Some(1234));
+
+        let (accepted, settings) = accept(
+            &requested,
+            Request::Read(None),
+            &limits,
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+        assert_eq!(settings.transfer_size, None);
+
+        let requested = options(&[("tsize", "4096")]);
+        let (accepted, settings) = accept(&requested, Request::Write, &limits, Settings::default());
+        assert_eq!(accepted, options(&[("tsize", "4096")]));
+        assert_eq!(settings.transfer_size, Some(4096));
+
+        let (accepted, _) = accept(
+            &requested,
+            Request::Read(Some(1234)),
+            &limits,
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+    }
+
+    #[test]
+    fn test_accept_timeout() {
+        let limits = Limits::default();
+
+        let adaptive = Settings {
+            timeout_bounds: Some((Duration::from_millis(10), Duration::from_secs(10))),
+            ..Settings::default()
+        };
+        let (accepted, settings) = accept(
+            &options(&[("timeout", "5")]),
+            Request::Write,
+            &limits,
+            adaptive,
+        );
+        assert_eq!(accepted, options(&[("timeout", "5")]));
+        assert_eq!(settings.timeout, Duration::from_secs(5));
+        assert_eq!(settings.timeout_bounds, None);
+
+        for bad in &["0", "256", "soon"] {
+            let (accepted, _) = accept(
+                &options(&[("timeout", bad)]),
+                Request::Write,
+                &limits,
+                Settings::default(),
+            );
+            assert!(accepted.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_accept_windowsize() {
+        let limits = Limits::default();
+
+        let (accepted, settings) = accept(
+            &options(&[("windowsize", "16")]),
+            Request::Write,
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("windowsize", "16")]));
+        assert_eq!(settings.window_size, 16);
+
+        let (accepted, settings) = accept(
+            &options(&[("windowsize", "1000")]),
+            Request::Write,
+            &limits,
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("windowsize", "64")]));
+        assert_eq!(settings.window_size, DEFAULT_MAX_WINDOW_SIZE);
+
+        for bad in &["0", "65536", "wide"] {
+            let (accepted, settings) = accept(
+                &options(&[("windowsize", bad)]),
+                Request::Write,
+                &limits,
+                Settings::default(),
+            );
+            assert!(accepted.is_empty());
+            assert_eq!(settings.window_size, 1);
+        }
+    }
+
+    #[test]
+    fn test_accept_rollover() {
+        let (accepted, settings) = accept(
+            &options(&[("rollover", "1")]),
+            Request::Read(None),
+            &Limits::default(),
+            Settings::default(),
+        );
+        assert_eq!(accepted, options(&[("rollover", "1")]));
+        assert_eq!(settings.rollover, Rollover::One);
+
+        let (accepted, settings) = accept(
+            &options(&[("rollover", "2")]),
+            Request::Read(None),
+            &Limits::default(),
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+        assert_eq!(settings.rollover, Rollover::Zero);
+    }
+
+    #[test]
+    fn test_accept_ignores_unknown_options() {
+        let (accepted, _) = accept(
+            &options(&[("x-unknown", "1")]),
+            Request::Write,
+            &Limits::default(),
+            Settings::default(),
+        );
+        assert!(accepted.is_empty());
+    }
+
+    #[test]
+    fn test_apply_blksize() {
+        let requested = options(&[("blksize", "1428")]);
+
+        let settings = apply(
+            &requested,
+            &options(&[("blksize", "1024")]),
+            Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(settings.block_size, 1024);
+
+        assert!(apply(
+            &requested,
+            &options(&[("blksize", "2048")]),
+            Settings::default()
+        )
+        .is_err());
+        assert!(apply(
+            &requested,
+            &options(&[("blksize", "0")]),
+            Settings::default()
+        )
+        .is_err());
+        assert!(apply(&requested, &options(&[("tsize", "0")]), Settings::default()).is_err());
+
+        let settings = apply(&requested, &Options::new(), Settings::default()).unwrap();
+        assert_eq!(settings.block_size, MAX_PAYLOAD_SIZE);
+    }
+
+    #[test]
+    fn test_apply_tsize_and_timeout() {
+        let requested = options(&[("tsize", "0"), ("timeout", "5")]);
+
+        let oack = options(&[("tsize", "1234"), ("timeout", "5")]);
+        let settings = apply(&requested, &oack, Settings::default()).unwrap();
+        assert_eq!(settings.transfer_size, Some(1234));
+        assert_eq!(settings.timeout, Duration::from_secs(5));
+
+        assert!(apply(
+            &requested,
+            &options(&[("tsize", "-1")]),
+            Settings::default()
+        )
+        .is_err());
+        assert!(apply(
+            &requested,
+            &options(&[("timeout", "6")]),
+            Settings::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_apply_windowsize() {
+        let requested = options(&[("windowsize", "16")]);
+
+        let settings = apply(
+            &requested,
+            &options(&[("windowsize", "8")]),
+            Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(settings.window_size, 8);
+
+        assert!(apply(
+            &requested,
+            &options(&[("windowsize", "32")]),
+            Settings::default()
+        )
+        .is_err());
+        assert!(apply(
+            &requested,
+            &options(&[("windowsize", "0")]),
+            Settings::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_apply_rollover() {
+        let requested = options(&[("rollover", "1")]);
+
+        let settings = apply(
+            &requested,
+            &options(&[("rollover", "1")]),
+            Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(settings.rollover, Rollover::One);
+
+        assert!(apply(
+            &requested,
+            &options(&[("rollover", "0")]),
+            Settings::default()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Translation between local text files and the netascii format used on the
+//! wire by transfers in `Mode::NetAscii` (RFC 1350, RFC 764).
+//!
+//! In netascii, every line ends with CR LF and a carriage return that is not
+//! part of a line ending is sent as CR NUL. Locally, lines end with a single
+//! LF.
+
+use std::io::{self, Read, Result, Write};
+
+const CR: u8 = b'\r';
+const LF: u8 = b'\n';
+const NUL: u8 = b'\0';
+
+/// Reads a local text file as netascii.
+///
+/// This is what a sender wraps its file in before handing it to a
+/// transfer.
+pub struct NetAsciiReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+
+    /// The second byte of an escape sequence that did not fit in the
+    /// caller's buffer.
+    pending: Option<u8>,
+}
+
+impl<R: Read> NetAsciiReader<R> {
+    /// Creates a new `NetAsciiReader` that translates the contents of
+    /// `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            pending: None,
+        }
+    }
+
+    /// Unwraps this `NetAsciiReader`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for NetAsciiReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> Result<usize> {
+        let mut written = 0;
+
+        while written < out.len() {
+            if let Some(byte) = self.pending.take() {
+                out[written] = byte;
+                written += 1;
+                continue;
+            }
+
+            if self.pos == self.buf.len() {
+                // Only block on the inner reader if nothing has been produced
+                // yet, like any other `Read` implementation.
+                if written > 0 {
+                    break;
+                }
+
+                self.buf.resize(out.len(), 0);
+                let nbytes = loop {
+                    match self.inner.read(&mut self.buf[..]) {
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        result => break result?,
+                    }
+                };
+                self.buf.truncate(nbytes);
+                self.pos = 0;
+
+                if nbytes == 0 {
+                    break;
+                }
+            }
+
+            let byte = self.buf[self.pos];
+            self.pos += 1;
+
+            let (first, second) = match byte {
+                LF => (CR, Some(LF)),
+                CR => (CR, Some(NUL)),
+                byte => (byte, None),
+            };
+            out[written] = first;
+            written += 1;
+            self.pending = second;
+        }
+
+        Ok(written)
+    }
+}
+
+/// Writes netascii received from the wire as a local text file.
+///
+/// This is what a receiver wraps its file in before handing it to a
+/// transfer. Call `finish` once the transfer is complete, since a trailing
+/// carriage return cannot be translated until it is known what follows it.
+pub struct NetAsciiWriter<W> {
+    inner: W,
+
+    /// Whether the last byte written was a carriage return that has not
+    /// been translated yet.
+    cr: bool,
+}
+
+impl<W: Write> NetAsciiWriter<W> {
+    /// Creates a new `NetAsciiWriter` that writes the translated contents
+    /// to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self { inner, cr: false }
+    }
+
+    /// Writes out anything still buffered and returns the underlying
+    /// writer.
+    pub fn finish(mut self) -> Result<W> {
+        if self.cr {
+            self.inner.write_all(&[CR])?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for NetAsciiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut translated = Vec::with_capacity(buf.len());
+
+        for &byte in buf {
+            if self.cr {
+                self.cr = false;
+                match byte {
+                    LF => translated.push(LF),
+                    NUL => translated.push(CR),
+                    // Not valid netascii, so keep the carriage return as is.
+                    byte => {
+                        translated.push(CR);
+                        self.cr = byte == CR;
+                        if !self.cr {
+                            translated.push(byte);
+                        }
+                    }
+                }
+            } else if byte == CR {
+                self.cr = true;
+            } else {
+                translated.push(byte);
+            }
+        }
+
+        self.inner.write_all(&translated[..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(input: &[u8], chunk: usize) -> Vec<u8> {
+        let mut reader = NetAsciiReader::new(input);
+        let mut encoded = Vec::new();
+        let mut buf = vec![0; chunk];
+        loop {
+            let nbytes = reader.read(&mut buf).unwrap();
+            if nbytes == 0 {
+                break;
+            }
+            encoded.extend_from_slice(&buf[..nbytes]);
+        }
+        encoded
+    }
+
+    fn decode(input: &[u8], chunk: usize) -> Vec<u8> {
+        let mut writer = NetAsciiWriter::new(Vec::new());
+        for piece in input.chunks(chunk) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_encode() {
+        let input = b"hostname switch\nbanner a\rb\n";
+        let expected = b"hostname switch\r\nbanner a\r\x00b\r\n";
+
+        for chunk in 1..8 {
+            assert_eq!(&encode(input, chunk)[..], &expected[..]);
+        }
+        assert!(encode(b"", 4).is_empty());
+    }
+
+    #[test]
+    fn test_decode() {
+        let input = b"hostname switch\r\nbanner a\r\x00b\r\n";
+        let expected = b"hostname switch\nbanner a\rb\n";
+
+        for chunk in 1..8 {
+            assert_eq!(&decode(input, chunk)[..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_decode_is_lenient() {
+        assert_eq!(&decode(b"a\rb", 1)[..], b"a\rb");
+        assert_eq!(&decode(b"a\r\r\n", 1)[..], b"a\r\n");
+        assert_eq!(&decode(b"a\r", 1)[..], b"a\r");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = b"\r\n\n\r\r\x00text\r";
+        assert_eq!(&decode(&encode(input, 3), 5)[..], &input[..]);
+    }
+}
@@ -1,10 +1,9 @@
 //! An `Ack` packet is a receipt for a successfully transmitted
 //! block.
 
-use std::io::{self, ErrorKind, Result};
 use std::mem::size_of;
 
-use super::Block;
+use super::{Block, DecodeError};
 use crate::bytes::{FromBytes, IntoBytes};
 use crate::packet::opcode::Opcode;
 use crate::packet::sealed::Packet;
@@ -28,19 +27,20 @@ impl Packet for Ack {
 }
 
 impl FromBytes for Ack {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
 
-        let split_at = size_of::<Block>();
-
-        if bytes.len() != split_at {
-            return Err(ErrorKind::InvalidInput.into());
+        if bytes.len() != size_of::<Block>() {
+            return Err(if bytes.len() < size_of::<Block>() {
+                DecodeError::Truncated
+            } else {
+                DecodeError::TrailingBytes
+            });
         }
 
-        let block = &bytes[..split_at];
-        let block = Block::from_bytes(block)?;
+        let block = Block::from_bytes(bytes)?;
 
         Ok(Self { block })
     }
@@ -62,8 +62,8 @@ mod tests {
         let actual = Ack::from_bytes(&input[..]).unwrap();
 
         assert_eq!(actual.block.0, 1);
-        assert!(Ack::from_bytes(&[1]).is_err());
-        assert!(Ack::from_bytes(&[1, 2, 3]).is_err());
+        assert_eq!(Ack::from_bytes([1]), Err(DecodeError::Truncated));
+        assert_eq!(Ack::from_bytes([1, 2, 3]), Err(DecodeError::TrailingBytes));
     }
 
     #[test]
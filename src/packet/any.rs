@@ -0,0 +1,185 @@
+//! An `AnyPacket` holds a packet of any type, which is useful when the type
+//! of a datagram is not known ahead of time.
+
+use super::*;
+
+/// A TFTP packet of any type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AnyPacket {
+    /// A read request.
+    Rrq(Packet<Rrq>),
+
+    /// A write request.
+    Wrq(Packet<Wrq>),
+
+    /// A block of data.
+    Data(Packet<Data>),
+
+    /// An acknowledgement of a block of data.
+    Ack(Packet<Ack>),
+
+    /// An error.
+    Error(Packet<Error>),
+
+    /// An acknowledgement of the options requested by the client.
+    Oack(Packet<Oack>),
+}
+
+impl AnyPacket {
+    /// Decodes a packet, dispatching on its opcode.
+    pub fn decode<B: AsRef<[u8]>>(bytes: B) -> std::result::Result<Self, DecodeError> {
+        Self::from_bytes(bytes)
+    }
+
+    /// Encodes the packet as a datagram.
+    pub fn encode(&self) -> std::result::Result<Vec<u8>, EncodeError> {
+        match self {
+            AnyPacket::Rrq(packet) => packet.encode(),
+            AnyPacket::Wrq(packet) => packet.encode(),
+            AnyPacket::Data(packet) => packet.encode(),
+            AnyPacket::Ack(packet) => packet.encode(),
+            AnyPacket::Error(packet) => packet.encode(),
+            AnyPacket::Oack(packet) => packet.encode(),
+        }
+    }
+
+    /// Encodes the packet into the beginning of `buf`, returning the number
+    /// of bytes written.
+    pub fn encode_into(&self, buf: &mut [u8]) -> std::result::Result<usize, EncodeError> {
+        let bytes = self.encode()?;
+        copy_into(&bytes, buf)
+    }
+
+    /// The opcode that identifies the type of this packet.
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            AnyPacket::Rrq(packet) => packet.header,
+            AnyPacket::Wrq(packet) => packet.header,
+            AnyPacket::Data(packet) => packet.header,
+            AnyPacket::Ack(packet) => packet.header,
+            AnyPacket::Error(packet) => packet.header,
+            AnyPacket::Oack(packet) => packet.header,
+        }
+    }
+}
+
+impl FromBytes for AnyPacket {
+    type Error = DecodeError;
+
+    fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> std::result::Result<Self, DecodeError> {
+        let bytes = bytes.as_ref();
+        if bytes.len() < size_of::<u16>() {
+            return Err(DecodeError::Truncated);
+        }
+
+        let (header, body) = bytes.split_at(size_of::<u16>());
+        let opcode = Opcode::from_bytes(header)?;
+
+        Ok(match opcode {
+            Opcode::Rrq => AnyPacket::Rrq(Packet::new(Rrq::from_bytes(body)?)),
+            Opcode::Wrq => AnyPacket::Wrq(Packet::new(Wrq::from_bytes(body)?)),
+            Opcode::Data => AnyPacket::Data(Packet::new(Data::from_bytes(body)?)),
+            Opcode::Ack => AnyPacket::Ack(Packet::new(Ack::from_bytes(body)?)),
+            Opcode::Error => AnyPacket::Error(Packet::new(Error::from_bytes(body)?)),
+            Opcode::Oack => AnyPacket::Oack(Packet::new(Oack::from_bytes(body)?)),
+        })
+    }
+}
+
+impl IntoBytes for AnyPacket {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            AnyPacket::Rrq(packet) => packet.into_bytes(),
+            AnyPacket::Wrq(packet) => packet.into_bytes(),
+            AnyPacket::Data(packet) => packet.into_bytes(),
+            AnyPacket::Ack(packet) => packet.into_bytes(),
+            AnyPacket::Error(packet) => packet.into_bytes(),
+            AnyPacket::Oack(packet) => packet.into_bytes(),
+        }
+    }
+}
+
+macro_rules! impl_from_packet {
+    ($($body:ident),*) => {
+        $(
+            impl From<Packet<$body>> for AnyPacket {
+                fn from(packet: Packet<$body>) -> Self {
+                    AnyPacket::$body(packet)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_packet!(Rrq, Wrq, Data, Ack, Error, Oack);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let actual = AnyPacket::decode(b"\0\x01file\0octet\0").unwrap();
+        assert_eq!(actual, AnyPacket::Rrq(Packet::rrq("file", Mode::Octet)));
+        assert_eq!(actual.opcode(), Opcode::Rrq);
+
+        let actual = AnyPacket::decode(b"\0\x02file\0netascii\0").unwrap();
+        assert_eq!(actual, AnyPacket::Wrq(Packet::wrq("file", Mode::NetAscii)));
+
+        let actual = AnyPacket::decode(b"\0\x03\0\x07abc").unwrap();
+        assert_eq!(actual, Packet::data(Block(7), b"abc").into());
+
+        let actual = AnyPacket::decode(b"\0\x04\0\x07").unwrap();
+        assert_eq!(actual, Packet::ack(Block(7)).into());
+
+        let actual = AnyPacket::decode(b"\0\x05\0\x01nope\0").unwrap();
+        assert_eq!(actual, Packet::error(Code::FileNotFound, "nope").into());
+
+        let actual = AnyPacket::decode(b"\0\x06tsize\x0042\0").unwrap();
+        let options = vec![("tsize", "42")].into_iter().collect();
+        assert_eq!(actual, Packet::oack(options).into());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(AnyPacket::decode(b""), Err(DecodeError::Truncated));
+        assert_eq!(AnyPacket::decode(b"\0"), Err(DecodeError::Truncated));
+        assert_eq!(
+            AnyPacket::decode(b"\0\x09"),
+            Err(DecodeError::UnknownOpcode(9))
+        );
+        assert_eq!(
+            AnyPacket::decode(b"\0\x01file"),
+            Err(DecodeError::MissingNul)
+        );
+        assert_eq!(
+            AnyPacket::decode(b"\0\x01file\0binary\0"),
+            Err(DecodeError::BadMode("binary".to_string()))
+        );
+        assert_eq!(
+            AnyPacket::decode(b"\0\x01file\0octet\0blksize\0"),
+            Err(DecodeError::MissingOptionValue("blksize".to_string()))
+        );
+        assert_eq!(
+            AnyPacket::decode(b"\0\x01\xff\0octet\0"),
+            Err(DecodeError::InvalidString)
+        );
+        assert_eq!(AnyPacket::decode(b"\0\x03\0"), Err(DecodeError::Truncated));
+        assert_eq!(AnyPacket::decode(b"\0\x04\0"), Err(DecodeError::Truncated));
+        assert_eq!(
+            AnyPacket::decode(b"\0\x04\0\x01\0"),
+            Err(DecodeError::TrailingBytes)
+        );
+        assert_eq!(AnyPacket::decode(b"\0\x05\0"), Err(DecodeError::Truncated));
+        assert_eq!(
+            AnyPacket::decode(b"\0\x05\0\x09oops\0"),
+            Err(DecodeError::UnknownErrorCode(9))
+        );
+    }
+
+    #[test]
+    fn test_into_bytes() {
+        let bytes = AnyPacket::from(Packet::ack(Block(7))).into_bytes();
+        assert_eq!(&bytes[..], b"\0\x04\0\x07");
+    }
+}
@@ -1,17 +1,17 @@
 //! A `Data` packet encapsulates a block of data.
 //!
-//! If a `Data` block contains less than 512 bytes as its payload,
-//! then it is the final `Data` block to be sent.
+//! If a `Data` block contains less than the block size (512 bytes unless
+//! negotiated otherwise) as its payload, then it is the final `Data` block
+//! to be sent.
 
-use std::io::{self, ErrorKind, Result};
 use std::mem::size_of;
 
-use super::Block;
+use super::{Block, DecodeError};
 use crate::bytes::{FromBytes, IntoBytes};
 use crate::packet::opcode::Opcode;
 use crate::packet::sealed::Packet;
 
-/// A vehicle for transmitting up to 512 bytes of a file.
+/// A vehicle for transmitting up to one block of a file.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Data {
     /// The identifier for this data.
@@ -36,14 +36,14 @@ impl Packet for Data {
 }
 
 impl FromBytes for Data {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
 
         let split_at = size_of::<Block>();
         if split_at > bytes.len() {
-            return Err(ErrorKind::InvalidInput.into());
+            return Err(DecodeError::Truncated);
         }
 
         let (block, data) = bytes.split_at(split_at);
@@ -70,7 +70,7 @@ mod tests {
 
     #[test]
     fn test_from_bytes() {
-        let input = vec![0x00, 0x01, b'p', b'o', b't', b'a', b't', b'o'];
+        let input = [0x00, 0x01, b'p', b'o', b't', b'a', b't', b'o'];
         let actual = Data::from_bytes(&input[..]).unwrap();
 
         assert_eq!(actual.block, Block(1));
@@ -82,7 +82,7 @@ mod tests {
         assert_eq!(actual.block, Block(2));
         assert_eq!(actual.data, &[]);
 
-        assert!(Data::from_bytes(&[0]).is_err());
+        assert_eq!(Data::from_bytes([0]), Err(DecodeError::Truncated));
     }
 
     #[test]
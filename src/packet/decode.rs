@@ -0,0 +1,69 @@
+//! The reasons a datagram cannot be decoded as a TFTP packet.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+use super::Opcode;
+
+/// Describes why a datagram is not a valid TFTP packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The opcode is not one of the known packet types.
+    UnknownOpcode(u16),
+
+    /// The packet is valid, but not of the type that was expected.
+    UnexpectedOpcode(Opcode),
+
+    /// The packet ends before all of its fixed-size fields.
+    Truncated,
+
+    /// The packet carries bytes after its last field.
+    TrailingBytes,
+
+    /// A string field is not terminated by a NUL byte.
+    MissingNul,
+
+    /// A string field is not valid UTF-8.
+    InvalidString,
+
+    /// The transfer mode of a request is not one of the known modes.
+    BadMode(String),
+
+    /// The error code of an `Error` packet is not one of the known codes.
+    UnknownErrorCode(u16),
+
+    /// An option name is not followed by a value.
+    MissingOptionValue(String),
+
+    /// An `Oack` packet acknowledges no options, which RFC 2347 does not
+    /// allow.
+    NoOptions,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownOpcode(opcode) => write!(f, "unknown opcode {}", opcode),
+            DecodeError::UnexpectedOpcode(opcode) => write!(f, "unexpected {} packet", opcode),
+            DecodeError::Truncated => write!(f, "packet is truncated"),
+            DecodeError::TrailingBytes => write!(f, "packet has trailing bytes"),
+            DecodeError::MissingNul => write!(f, "string is not NUL-terminated"),
+            DecodeError::InvalidString => write!(f, "string is not valid UTF-8"),
+            DecodeError::BadMode(mode) => write!(f, "unknown transfer mode {:?}", mode),
+            DecodeError::UnknownErrorCode(code) => write!(f, "unknown error code {}", code),
+            DecodeError::MissingOptionValue(name) => {
+                write!(f, "option {:?} is missing a value", name)
+            }
+            DecodeError::NoOptions => write!(f, "OACK acknowledges no options"),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+impl From<DecodeError> for io::Error {
+    fn from(err: DecodeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
@@ -0,0 +1,46 @@
+//! The reasons a packet cannot be encoded as a datagram.
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Describes why a packet cannot be encoded.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EncodeError {
+    /// A string field contains a NUL byte, which would end it early on the
+    /// wire.
+    InteriorNul,
+
+    /// The buffer given to `encode_into` cannot hold the whole packet.
+    BufferTooSmall {
+        /// The number of bytes the packet takes up.
+        needed: usize,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::InteriorNul => write!(f, "string contains a NUL byte"),
+            EncodeError::BufferTooSmall { needed } => {
+                write!(f, "buffer is too small for a {} byte packet", needed)
+            }
+        }
+    }
+}
+
+impl error::Error for EncodeError {}
+
+impl From<EncodeError> for io::Error {
+    fn from(err: EncodeError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Checks that `s` can be sent as a NUL-terminated string.
+pub(crate) fn check_str(s: &str) -> Result<(), EncodeError> {
+    if s.contains('\0') {
+        return Err(EncodeError::InteriorNul);
+    }
+    Ok(())
+}
@@ -2,14 +2,16 @@
 
 use std::convert::AsRef;
 use std::fmt;
-use std::io::{self, ErrorKind, Result};
+use std::io::{ErrorKind, Result};
 use std::mem::size_of;
 
+use super::encode::check_str;
+use super::{DecodeError, EncodeError};
 use crate::bytes::{Bytes, FromBytes, IntoBytes};
 use crate::packet::opcode::Opcode;
 use crate::packet::sealed::Packet;
 
-/// Error codes defined by RFC 1350.
+/// Error codes defined by RFC 1350 and RFC 2347.
 #[allow(missing_docs)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Code {
@@ -21,20 +23,22 @@ pub enum Code {
     UnknownTid = 5,
     FileAlreadyExists = 6,
     NoSuchUser = 7,
+    OptionNegotiation = 8,
 }
 
 impl Code {
     /// Attempts to convert a `u16` into an error `Code`.
     pub fn from_u16(val: u16) -> Result<Self> {
         Ok(match val {
-            v if v == 0 => Code::NotDefined,
-            v if v == 1 => Code::FileNotFound,
-            v if v == 2 => Code::AccessViolation,
-            v if v == 3 => Code::DiskFull,
-            v if v == 4 => Code::IllegalOperation,
-            v if v == 5 => Code::UnknownTid,
-            v if v == 6 => Code::FileAlreadyExists,
-            v if v == 7 => Code::NoSuchUser,
+            0 => Code::NotDefined,
+            1 => Code::FileNotFound,
+            2 => Code::AccessViolation,
+            3 => Code::DiskFull,
+            4 => Code::IllegalOperation,
+            5 => Code::UnknownTid,
+            6 => Code::FileAlreadyExists,
+            7 => Code::NoSuchUser,
+            8 => Code::OptionNegotiation,
             _ => return Err(ErrorKind::InvalidInput.into()),
         })
     }
@@ -50,6 +54,7 @@ impl Code {
             Code::UnknownTid => "Unknown transfer ID",
             Code::FileAlreadyExists => "File already exists",
             Code::NoSuchUser => "No such user",
+            Code::OptionNegotiation => "Option negotiation failed",
         }
     }
 }
@@ -63,12 +68,11 @@ impl IntoBytes for Code {
 }
 
 impl FromBytes for Code {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
-        let bytes = Bytes::from_bytes(bytes)?;
-        let code = Code::from_u16(bytes.into_inner())?;
-        Ok(code)
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> std::result::Result<Self, DecodeError> {
+        let val = Bytes::from_bytes(bytes)?.into_inner();
+        Code::from_u16(val).map_err(|_| DecodeError::UnknownErrorCode(val))
     }
 }
 
@@ -100,13 +104,21 @@ impl Error {
 
 impl Packet for Error {
     const OPCODE: Opcode = Opcode::Error;
+
+    fn check(&self) -> std::result::Result<(), EncodeError> {
+        check_str(&self.message)
+    }
 }
 
 impl FromBytes for Error {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> std::result::Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
+        if bytes.len() < size_of::<u16>() {
+            return Err(DecodeError::Truncated);
+        }
+
         let (code, message) = bytes.split_at(size_of::<u16>());
         let code = Code::from_bytes(code)?;
         let message = Bytes::from_bytes(message)?;
@@ -139,7 +151,8 @@ mod tests {
         assert_eq!(Code::from_u16(5).unwrap(), Code::UnknownTid);
         assert_eq!(Code::from_u16(6).unwrap(), Code::FileAlreadyExists);
         assert_eq!(Code::from_u16(7).unwrap(), Code::NoSuchUser);
-        assert!(Code::from_u16(8).is_err());
+        assert_eq!(Code::from_u16(8).unwrap(), Code::OptionNegotiation);
+        assert!(Code::from_u16(9).is_err());
     }
 
     #[test]
@@ -155,8 +168,12 @@ mod tests {
         assert_eq!(actual.code, Code::NotDefined);
         assert_eq!(actual.message.as_str(), "");
 
-        assert!(Error::from_bytes(&[0, 1]).is_err());
-        assert!(Error::from_bytes(&[2, b'\0']).is_err());
+        assert_eq!(Error::from_bytes([0, 1]), Err(DecodeError::MissingNul));
+        assert_eq!(
+            Error::from_bytes([2, b'\0']),
+            Err(DecodeError::UnknownErrorCode(512))
+        );
+        assert_eq!(Error::from_bytes([2]), Err(DecodeError::Truncated));
     }
 
     #[test]
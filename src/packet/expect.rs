@@ -2,11 +2,11 @@
 //! an error.
 
 use std::io::Result;
-use std::net::UdpSocket;
+use std::net::{SocketAddr, UdpSocket};
 
-use super::Packet;
+use super::{AnyPacket, Packet};
 use crate::bytes::{FromBytes, IntoBytes};
-use crate::packet::{error, Error};
+use crate::packet::error;
 
 /// Implementors can attempt to produce a packet of a certain type from
 /// the provided bytes.
@@ -27,25 +27,55 @@ impl ExpectPacket for UdpSocket {
         &self,
         bytes: B,
     ) -> Result<Packet<P>> {
-        let bytes = bytes.as_ref();
-        match Packet::<P>::from_bytes(&bytes) {
-            // Yay
-            Ok(packet) => Ok(packet),
-            Err(_) => {
-                // If we didn't get the packet we were expecting, maybe the
-                // peer sent us an error packet.
-                if let Ok(err_pkt) = Packet::<Error>::from_bytes(&bytes) {
-                    Err(err_pkt.into())
-                } else {
-                    // Peer didn't send us the expected packet OR an error
-                    // packet. Send them our own error packet and terminate
-                    // the connection.
-                    let kind = error::Code::IllegalOperation;
-                    let err = Packet::error(kind, kind.as_str());
-                    let bytes = err.clone().into_bytes();
-                    let _ = self.send(&bytes[..]);
-                    Err(err.into())
-                }
+        expect_packet(bytes.as_ref(), |reply| self.send(reply))
+    }
+}
+
+/// An unconnected socket along with the peer it is exchanging packets with.
+pub(crate) struct Peer<'a> {
+    socket: &'a UdpSocket,
+    addr: SocketAddr,
+}
+
+impl<'a> Peer<'a> {
+    pub(crate) fn new(socket: &'a UdpSocket, addr: SocketAddr) -> Self {
+        Self { socket, addr }
+    }
+}
+
+impl ExpectPacket for Peer<'_> {
+    fn expect_packet<P: super::sealed::Packet, B: AsRef<[u8]>>(
+        &self,
+        bytes: B,
+    ) -> Result<Packet<P>> {
+        expect_packet(bytes.as_ref(), |reply| {
+            self.socket.send_to(reply, self.addr)
+        })
+    }
+}
+
+fn expect_packet<P, F>(bytes: &[u8], send: F) -> Result<Packet<P>>
+where
+    P: super::sealed::Packet,
+    F: FnOnce(&[u8]) -> Result<usize>,
+{
+    match Packet::<P>::from_bytes(bytes) {
+        // Yay
+        Ok(packet) => Ok(packet),
+        Err(reason) => {
+            // If we didn't get the packet we were expecting, maybe the
+            // peer sent us an error packet.
+            if let Ok(AnyPacket::Error(err_pkt)) = AnyPacket::decode(bytes) {
+                Err(err_pkt.into())
+            } else {
+                // Peer didn't send us the expected packet OR an error
+                // packet. Send them our own error packet and terminate
+                // the connection.
+                let kind = error::Code::IllegalOperation;
+                let err = Packet::error(kind, format!("{}: {}", kind, reason));
+                let bytes = err.clone().into_bytes();
+                let _ = send(&bytes[..]);
+                Err(err.into())
             }
         }
     }
@@ -1,37 +1,60 @@
 //! Parsing and creating TFTP packets.
 
 use std::convert::AsRef;
-use std::io::{self, ErrorKind, Result};
+use std::io::{self, ErrorKind};
 use std::mem::size_of;
 
 use crate::bytes::{Bytes, FromBytes, IntoBytes};
 pub use ack::Ack;
+pub use any::AnyPacket;
 pub use data::Data;
+pub use decode::DecodeError;
+pub use encode::EncodeError;
 pub use error::{Code, Error};
 pub use mode::Mode;
+pub use oack::Oack;
 pub use opcode::Opcode;
+pub use options::Options;
 pub use rq::{Rrq, Wrq};
 
 mod ack;
+mod any;
 mod data;
+mod decode;
+mod encode;
 mod error;
 pub mod expect;
 mod mode;
+mod oack;
 mod opcode;
+mod options;
 mod rq;
 
-/// The maximum number of bytes carried in a `Data` packet.
+/// The maximum number of bytes carried in a `Data` packet, unless a
+/// different block size has been negotiated.
 pub const MAX_PAYLOAD_SIZE: usize = 512;
 
 /// The total size of a TFTP packet. (512 + 2 byte opcode + 2 byte block ID)
 pub const MAX_PACKET_SIZE: usize = 516;
 
+/// The smallest block size that may be negotiated (RFC 2348).
+pub const MIN_BLOCK_SIZE: usize = 8;
+
+/// The largest block size that may be negotiated (RFC 2348).
+pub const MAX_BLOCK_SIZE: usize = 65464;
+
 mod sealed {
     use crate::bytes::{FromBytes, IntoBytes};
     use crate::packet::opcode::Opcode;
+    use crate::packet::{DecodeError, EncodeError};
 
-    pub trait Packet: FromBytes + IntoBytes {
+    pub trait Packet: FromBytes<Error = DecodeError> + IntoBytes + Clone {
         const OPCODE: Opcode;
+
+        /// Checks that the packet can be encoded without losing any of it.
+        fn check(&self) -> Result<(), EncodeError> {
+            Ok(())
+        }
     }
 }
 
@@ -44,12 +67,52 @@ impl Block {
     pub fn new(val: u16) -> Self {
         Self(val)
     }
+
+    /// Maps the position of a block within a transfer, counting from 1, to
+    /// the block number that identifies it on the wire.
+    ///
+    /// Transfers of more than 65535 blocks wrap around as `rollover`
+    /// describes. Position 0 maps to block 0, which acknowledges an `Oack`.
+    pub fn from_sequence(seq: u64, rollover: Rollover) -> Self {
+        let wire = match rollover {
+            Rollover::Zero => seq % 0x1_0000,
+            Rollover::One if seq == 0 => 0,
+            Rollover::One => (seq - 1) % 0xffff + 1,
+        };
+
+        Self(wire as u16)
+    }
+
+    /// Counts how many blocks after `base` this block comes, wrapping
+    /// around as `rollover` describes.
+    pub(crate) fn offset_from(self, base: Block, rollover: Rollover) -> u32 {
+        let (block, base) = (u32::from(self.0), u32::from(base.0));
+        match rollover {
+            Rollover::One if block != 0 && base != 0 => (block + 0xffff - base) % 0xffff,
+            _ => (block + 0x1_0000 - base) % 0x1_0000,
+        }
+    }
+}
+
+/// What the block number wraps around to after block 65535.
+///
+/// RFC 1350 leaves this undefined, so transfers of more than 65535 blocks
+/// only work if both peers agree. The de-facto `rollover` option lets the
+/// client pick one.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Rollover {
+    /// Block 65535 is followed by block 0. Most implementations do this.
+    #[default]
+    Zero,
+
+    /// Block 65535 is followed by block 1.
+    One,
 }
 
 impl FromBytes for Block {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
         let block = Bytes::from_bytes(bytes)?;
 
@@ -81,6 +144,36 @@ impl<T: sealed::Packet> Packet<T> {
             body,
         }
     }
+
+    /// Decodes a packet of this type from a datagram.
+    pub fn decode<B: AsRef<[u8]>>(bytes: B) -> Result<Self, DecodeError> {
+        Self::from_bytes(bytes)
+    }
+
+    /// Encodes the packet as a datagram.
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        self.body.check()?;
+        Ok(self.clone().into_bytes())
+    }
+
+    /// Encodes the packet into the beginning of `buf`, returning the number
+    /// of bytes written.
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        let bytes = self.encode()?;
+        copy_into(&bytes, buf)
+    }
+}
+
+/// Copies an encoded packet into a caller-supplied buffer.
+fn copy_into(bytes: &[u8], buf: &mut [u8]) -> Result<usize, EncodeError> {
+    let needed = bytes.len();
+    match buf.get_mut(..needed) {
+        Some(dest) => {
+            dest.copy_from_slice(bytes);
+            Ok(needed)
+        }
+        None => Err(EncodeError::BufferTooSmall { needed }),
+    }
 }
 
 impl Packet<Rrq> {
@@ -90,6 +183,13 @@ impl Packet<Rrq> {
 
         Self::new(rrq)
     }
+
+    /// Creates a new read request packet that requests the given options.
+    pub fn rrq_with_options<T: AsRef<str>>(filename: T, mode: Mode, options: Options) -> Self {
+        let rrq = Rrq::with_options(filename, mode, options);
+
+        Self::new(rrq)
+    }
 }
 
 impl Packet<Wrq> {
@@ -99,6 +199,13 @@ impl Packet<Wrq> {
 
         Self::new(wrq)
     }
+
+    /// Creates a new write request packet that requests the given options.
+    pub fn wrq_with_options<T: AsRef<str>>(filename: T, mode: Mode, options: Options) -> Self {
+        let wrq = Wrq::with_options(filename, mode, options);
+
+        Self::new(wrq)
+    }
 }
 
 impl Packet<Data> {
@@ -119,6 +226,15 @@ impl Packet<Ack> {
     }
 }
 
+impl Packet<Oack> {
+    /// Creates a new option acknowledgement packet.
+    pub fn oack(options: Options) -> Self {
+        let oack = Oack::new(options);
+
+        Self::new(oack)
+    }
+}
+
 impl Packet<Error> {
     /// Creates a new error packet.
     pub fn error<T: AsRef<str>>(code: Code, message: T) -> Self {
@@ -169,19 +285,21 @@ impl From<Packet<Error>> for io::Error {
 }
 
 impl<T: sealed::Packet> FromBytes for Packet<T> {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Result<Self> {
+    fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
+        if bytes.len() < size_of::<u16>() {
+            return Err(DecodeError::Truncated);
+        }
+
         let (header, body) = bytes.split_at(size_of::<u16>());
         let opcode = Opcode::from_bytes(header)?;
         if opcode != T::OPCODE {
-            return Err(ErrorKind::InvalidData.into());
+            return Err(DecodeError::UnexpectedOpcode(opcode));
         }
 
-        /* FIXME: Remove map_err and just use `?` */
-        let body =
-            T::from_bytes(body).map_err(|_| -> io::Error { ErrorKind::InvalidData.into() })?;
+        let body = T::from_bytes(body)?;
 
         Ok(Self {
             header: opcode,
@@ -241,7 +359,7 @@ mod tests {
 
     #[test]
     fn test_data() {
-        let data = Packet::data(Block(25), &[1, 2, 3]);
+        let data = Packet::data(Block(25), [1, 2, 3]);
         assert_eq!(data.header, Opcode::Data);
 
         let op = vec![0, 3];
@@ -252,7 +370,7 @@ mod tests {
         bytes.append(&mut dat);
         assert_eq!(bytes, data.into_bytes());
 
-        let expected = Packet::data(Block(25), &[1, 2, 3]);
+        let expected = Packet::data(Block(25), [1, 2, 3]);
         let actual = Packet::<Data>::from_bytes(&bytes[..]).unwrap();
         assert_eq!(expected, actual);
     }
@@ -291,6 +409,110 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_oack() {
+        let options: Options = vec![("blksize", "1024")].into_iter().collect();
+        let oack = Packet::oack(options.clone());
+        assert_eq!(oack.header, Opcode::Oack);
+
+        let op = vec![0, 6];
+        let mut opts = b"blksize\x001024\0".to_vec();
+        let mut bytes = op;
+        bytes.append(&mut opts);
+        assert_eq!(bytes, oack.into_bytes());
+
+        let expected = Packet::oack(options);
+        let actual = Packet::<Oack>::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_rrq_with_options() {
+        let options: Options = vec![("blksize", "1024"), ("tsize", "0")]
+            .into_iter()
+            .collect();
+        let rrq = Packet::rrq_with_options("pxelinux.0", Mode::Octet, options);
+
+        let bytes = rrq.clone().into_bytes();
+        assert_eq!(
+            &bytes[..],
+            &b"\0\x01pxelinux.0\0octet\0blksize\x001024\0tsize\x000\0"[..]
+        );
+
+        let actual = Packet::<Rrq>::from_bytes(&bytes[..]).unwrap();
+        assert_eq!(rrq, actual);
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let rrq = Packet::rrq("pxelinux.0", Mode::Octet);
+        let bytes = rrq.encode().unwrap();
+        assert_eq!(&bytes[..], b"\0\x01pxelinux.0\0octet\0");
+        assert_eq!(Packet::<Rrq>::decode(&bytes[..]).unwrap(), rrq);
+
+        assert_eq!(
+            Packet::<Ack>::decode(&bytes[..]),
+            Err(DecodeError::UnexpectedOpcode(Opcode::Rrq))
+        );
+        assert_eq!(Packet::<Ack>::decode([0]), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let ack = Packet::ack(Block(9));
+
+        let mut buf = [0xff; 8];
+        assert_eq!(ack.encode_into(&mut buf), Ok(4));
+        assert_eq!(&buf, &[0, 4, 0, 9, 0xff, 0xff, 0xff, 0xff]);
+
+        let mut buf = [0; 3];
+        assert_eq!(
+            ack.encode_into(&mut buf),
+            Err(EncodeError::BufferTooSmall { needed: 4 })
+        );
+    }
+
+    #[test]
+    fn test_encode_rejects_interior_nul() {
+        let rrq = Packet::rrq("evil\0name", Mode::Octet);
+        assert_eq!(rrq.encode(), Err(EncodeError::InteriorNul));
+
+        let error = Packet::error(Code::NotDefined, "bad\0message");
+        assert_eq!(error.encode(), Err(EncodeError::InteriorNul));
+
+        let options = vec![("blksize", "10\x0024")].into_iter().collect();
+        let oack = Packet::oack(options);
+        assert_eq!(oack.encode(), Err(EncodeError::InteriorNul));
+    }
+
+    #[test]
+    fn test_block_offset_from() {
+        assert_eq!(Block(5).offset_from(Block(5), Rollover::Zero), 0);
+        assert_eq!(Block(7).offset_from(Block(5), Rollover::Zero), 2);
+        assert_eq!(Block(4).offset_from(Block(5), Rollover::Zero), 65535);
+        assert_eq!(Block(1).offset_from(Block(65535), Rollover::Zero), 2);
+
+        assert_eq!(Block(7).offset_from(Block(5), Rollover::One), 2);
+        assert_eq!(Block(4).offset_from(Block(5), Rollover::One), 65534);
+        assert_eq!(Block(1).offset_from(Block(65535), Rollover::One), 1);
+    }
+
+    #[test]
+    fn test_block_from_sequence() {
+        assert_eq!(Block::from_sequence(0, Rollover::Zero), Block(0));
+        assert_eq!(Block::from_sequence(1, Rollover::Zero), Block(1));
+        assert_eq!(Block::from_sequence(65535, Rollover::Zero), Block(65535));
+        assert_eq!(Block::from_sequence(65536, Rollover::Zero), Block(0));
+        assert_eq!(Block::from_sequence(65537, Rollover::Zero), Block(1));
+
+        assert_eq!(Block::from_sequence(0, Rollover::One), Block(0));
+        assert_eq!(Block::from_sequence(1, Rollover::One), Block(1));
+        assert_eq!(Block::from_sequence(65535, Rollover::One), Block(65535));
+        assert_eq!(Block::from_sequence(65536, Rollover::One), Block(1));
+        assert_eq!(Block::from_sequence(131070, Rollover::One), Block(65535));
+        assert_eq!(Block::from_sequence(131071, Rollover::One), Block(1));
+    }
+
     #[test]
     fn test_ack_from_data() {
         let block_number = 129;
@@ -6,6 +6,7 @@ use std::fmt;
 use std::io::{self, ErrorKind, Result};
 use std::str::FromStr;
 
+use super::DecodeError;
 use crate::bytes::{Bytes, FromBytes, IntoBytes};
 
 /// The modes of operation for TFTP.
@@ -42,14 +43,14 @@ impl IntoBytes for Mode {
 }
 
 impl FromBytes for Mode {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> std::result::Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
         let s = Bytes::from_bytes(bytes)?;
         let s: String = s.into_inner();
 
-        Mode::from_str(&s)
+        Mode::from_str(&s).map_err(|_| DecodeError::BadMode(s))
     }
 }
 
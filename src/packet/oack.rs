@@ -0,0 +1,74 @@
+//! An `Oack` packet acknowledges the options (RFC 2347) that the server
+//! has agreed to use for a transfer.
+
+use super::{DecodeError, EncodeError, Options};
+use crate::bytes::{FromBytes, IntoBytes};
+use crate::packet::opcode::Opcode;
+use crate::packet::sealed::Packet;
+
+/// An acknowledgement of the options requested by the client.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Oack {
+    /// The options the server has accepted, along with their agreed values.
+    pub options: Options,
+}
+
+impl Oack {
+    /// Creates a new `Oack` packet.
+    pub fn new(options: Options) -> Self {
+        Self { options }
+    }
+}
+
+impl Packet for Oack {
+    const OPCODE: Opcode = Opcode::Oack;
+
+    fn check(&self) -> Result<(), EncodeError> {
+        self.options.check()
+    }
+}
+
+impl FromBytes for Oack {
+    type Error = DecodeError;
+
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
+        let options = Options::from_bytes(bytes)?;
+        if options.is_empty() {
+            return Err(DecodeError::NoOptions);
+        }
+
+        Ok(Self { options })
+    }
+}
+
+impl IntoBytes for Oack {
+    fn into_bytes(self) -> Vec<u8> {
+        self.options.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let actual = Oack::from_bytes(b"blksize\x001024\0").unwrap();
+        assert_eq!(actual.options.get("blksize"), Some("1024"));
+
+        assert!(Oack::from_bytes(b"blksize\0").is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_requires_an_option() {
+        assert_eq!(Oack::from_bytes(b""), Err(DecodeError::NoOptions));
+    }
+
+    #[test]
+    fn test_into_bytes() {
+        let oack = Oack::new(vec![("timeout", "5")].into_iter().collect());
+
+        let bytes = oack.into_bytes();
+        assert_eq!(&bytes[..], b"timeout\x005\0");
+    }
+}
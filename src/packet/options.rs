@@ -0,0 +1,149 @@
+//! Option extensions (RFC 2347) appended to requests and echoed back in
+//! `Oack` packets.
+
+use super::encode::check_str;
+use super::{DecodeError, EncodeError};
+use crate::bytes::{Bytes, FirstNul, FromBytes, IntoBytes};
+
+/// An ordered list of option name/value pairs.
+///
+/// Option names are compared case-insensitively.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Options(Vec<(String, String)>);
+
+impl Options {
+    /// Creates an empty list of options.
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Appends an option to the list.
+    pub fn push<N: AsRef<str>, V: AsRef<str>>(&mut self, name: N, value: V) {
+        self.0
+            .push((name.as_ref().to_string(), value.as_ref().to_string()));
+    }
+
+    /// Returns the value of the first option called `name`, if any.
+    pub fn get<N: AsRef<str>>(&self, name: N) -> Option<&str> {
+        let name = name.as_ref();
+        self.0
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns `true` if an option called `name` is present.
+    pub fn contains<N: AsRef<str>>(&self, name: N) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Iterates over the options in the order they appear on the wire.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// The number of options in the list.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no options.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn check(&self) -> Result<(), EncodeError> {
+        for (name, value) in self.iter() {
+            check_str(name)?;
+            check_str(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromBytes for Options {
+    type Error = DecodeError;
+
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
+        let mut bytes = bytes.as_ref();
+        let mut strings = Vec::new();
+
+        while !bytes.is_empty() {
+            let first_nul = match bytes.first_nul_idx() {
+                Some(idx) => idx,
+                None => return Err(DecodeError::MissingNul),
+            };
+
+            let (s, rest) = bytes.split_at(first_nul + 1);
+            let s: Bytes<String> = Bytes::from_bytes(s)?;
+            strings.push(s.into_inner());
+            bytes = rest;
+        }
+
+        if strings.len() % 2 != 0 {
+            let name = strings.pop().unwrap_or_default();
+            return Err(DecodeError::MissingOptionValue(name));
+        }
+
+        let mut options = Options::new();
+        for pair in strings.chunks(2) {
+            options.push(&pair[0], &pair[1]);
+        }
+
+        Ok(options)
+    }
+}
+
+impl IntoBytes for Options {
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (name, value) in self.0 {
+            bytes.append(&mut Bytes::new(name).into_bytes());
+            bytes.append(&mut Bytes::new(value).into_bytes());
+        }
+        bytes
+    }
+}
+
+impl<N: AsRef<str>, V: AsRef<str>> std::iter::FromIterator<(N, V)> for Options {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut options = Options::new();
+        for (name, value) in iter {
+            options.push(name, value);
+        }
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        let actual = Options::from_bytes(b"blksize\x001428\0tsize\x000\0").unwrap();
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual.get("blksize"), Some("1428"));
+        assert_eq!(actual.get("TSIZE"), Some("0"));
+
+        assert!(Options::from_bytes(b"").unwrap().is_empty());
+        assert_eq!(
+            Options::from_bytes(b"blksize\0"),
+            Err(DecodeError::MissingOptionValue("blksize".to_string()))
+        );
+        assert_eq!(
+            Options::from_bytes(b"blksize\x001428"),
+            Err(DecodeError::MissingNul)
+        );
+    }
+
+    #[test]
+    fn test_into_bytes() {
+        let options: Options = vec![("blksize", "1428"), ("tsize", "0")]
+            .into_iter()
+            .collect();
+
+        let bytes = options.into_bytes();
+        assert_eq!(&bytes[..], b"blksize\x001428\0tsize\x000\0");
+    }
+}
@@ -3,9 +3,9 @@
 //! This module is meant to be specialized by submodules and
 //! therefore it is not meant to be used directly.
 
-use std::io::{self, ErrorKind, Result};
-
+use super::encode::check_str;
 use super::mode::Mode;
+use super::{DecodeError, EncodeError, Options};
 use crate::bytes::{Bytes, FirstNul, FromBytes, IntoBytes};
 
 mod rrq;
@@ -18,27 +18,47 @@ pub use wrq::Wrq;
 pub struct Rq {
     pub filename: String,
     pub mode: Mode,
+    pub options: Options,
+}
+
+impl Rq {
+    pub(crate) fn check(&self) -> Result<(), EncodeError> {
+        check_str(&self.filename)?;
+        self.options.check()
+    }
 }
 
 impl FromBytes for Rq {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let bytes = bytes.as_ref();
 
         let first_nul = match bytes.first_nul_idx() {
             Some(idx) => idx,
-            None => return Err(ErrorKind::InvalidInput.into()),
+            None => return Err(DecodeError::MissingNul),
         };
 
         /* want to include the nul byte of the filename in its slice */
         let split_at = first_nul + 1;
-        let (filename, mode) = bytes.split_at(split_at);
+        let (filename, rest) = bytes.split_at(split_at);
         let filename = Bytes::from_bytes(filename)?;
         let filename = filename.into_inner();
+
+        let second_nul = match rest.first_nul_idx() {
+            Some(idx) => idx,
+            None => return Err(DecodeError::MissingNul),
+        };
+
+        let (mode, options) = rest.split_at(second_nul + 1);
         let mode = Mode::from_bytes(mode)?;
+        let options = Options::from_bytes(options)?;
 
-        Ok(Self { filename, mode })
+        Ok(Self {
+            filename,
+            mode,
+            options,
+        })
     }
 }
 
@@ -46,9 +66,11 @@ impl IntoBytes for Rq {
     fn into_bytes(self) -> Vec<u8> {
         let filename = Bytes::new(self.filename).into_bytes();
         let mut mode = self.mode.into_bytes();
+        let mut options = self.options.into_bytes();
 
         let mut bytes = filename;
         bytes.append(&mut mode);
+        bytes.append(&mut options);
         bytes
     }
 }
@@ -65,9 +87,33 @@ mod tests {
         assert_eq!(actual.filename.as_str(), "alice-in-wonderland.txt");
         assert_eq!(actual.mode, Mode::NetAscii);
 
-        assert!(Rq::from_bytes(b"no-nul").is_err());
-        assert!(Rq::from_bytes(b"only-filename-here\0").is_err());
-        assert!(Rq::from_bytes(b"only-filename-here\0nonul").is_err());
+        assert_eq!(Rq::from_bytes(b"no-nul"), Err(DecodeError::MissingNul));
+        assert_eq!(
+            Rq::from_bytes(b"only-filename-here\0"),
+            Err(DecodeError::MissingNul)
+        );
+        assert_eq!(
+            Rq::from_bytes(b"only-filename-here\0nonul"),
+            Err(DecodeError::MissingNul)
+        );
+        assert_eq!(
+            Rq::from_bytes(b"file\0binary\0"),
+            Err(DecodeError::BadMode("binary".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_with_options() {
+        let input = b"pxelinux.0\0octet\0blksize\x001468\0tsize\x000\0";
+        let actual = Rq::from_bytes(&input[..]).unwrap();
+
+        assert_eq!(actual.filename.as_str(), "pxelinux.0");
+        assert_eq!(actual.mode, Mode::Octet);
+        assert_eq!(actual.options.len(), 2);
+        assert_eq!(actual.options.get("blksize"), Some("1468"));
+        assert_eq!(actual.options.get("tsize"), Some("0"));
+
+        assert!(Rq::from_bytes(b"pxelinux.0\0octet\0blksize\0").is_err());
     }
 
     #[test]
@@ -75,9 +121,19 @@ mod tests {
         let rq = Rq {
             filename: "alice-in-wonderland.txt".to_string(),
             mode: Mode::Octet,
+            options: Options::new(),
         };
 
         let bytes = rq.into_bytes();
         assert_eq!(&bytes[..], b"alice-in-wonderland.txt\0octet\0");
+
+        let rq = Rq {
+            filename: "pxelinux.0".to_string(),
+            mode: Mode::Octet,
+            options: vec![("blksize", "1468")].into_iter().collect(),
+        };
+
+        let bytes = rq.into_bytes();
+        assert_eq!(&bytes[..], b"pxelinux.0\0octet\0blksize\x001468\0");
     }
 }
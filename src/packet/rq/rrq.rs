@@ -1,12 +1,12 @@
 //! A Read Request indicates that a peer wants to receive a file.
 
-use std::io::{self, Result};
-
 use super::Rq;
 use crate::bytes::{FromBytes, IntoBytes};
 use crate::packet::mode::Mode;
 use crate::packet::opcode::Opcode;
+use crate::packet::options::Options;
 use crate::packet::sealed::Packet;
+use crate::packet::{DecodeError, EncodeError};
 
 /// A read request.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -15,19 +15,32 @@ pub struct Rrq(pub Rq);
 impl Rrq {
     /// Creates a new `Rrq`.
     pub fn new<T: AsRef<str>>(filename: T, mode: Mode) -> Self {
+        Self::with_options(filename, mode, Options::new())
+    }
+
+    /// Creates a new `Rrq` that requests the given options.
+    pub fn with_options<T: AsRef<str>>(filename: T, mode: Mode, options: Options) -> Self {
         let filename = filename.as_ref().to_string();
-        Self(Rq { filename, mode })
+        Self(Rq {
+            filename,
+            mode,
+            options,
+        })
     }
 }
 
 impl Packet for Rrq {
     const OPCODE: Opcode = Opcode::Rrq;
+
+    fn check(&self) -> Result<(), EncodeError> {
+        self.0.check()
+    }
 }
 
 impl FromBytes for Rrq {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let rq = Rq::from_bytes(bytes)?;
 
         Ok(Self(rq))
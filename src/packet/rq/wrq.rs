@@ -1,12 +1,12 @@
 //! A Write Request indicates the peer wants to transmit a file.
 
-use std::io::{self, Result};
-
 use super::Rq;
 use crate::bytes::{FromBytes, IntoBytes};
 use crate::packet::mode::Mode;
 use crate::packet::opcode::Opcode;
+use crate::packet::options::Options;
 use crate::packet::sealed::Packet;
+use crate::packet::{DecodeError, EncodeError};
 
 /// A write request.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -15,19 +15,32 @@ pub struct Wrq(pub Rq);
 impl Wrq {
     /// Creates a new `Wrq`.
     pub fn new<T: AsRef<str>>(filename: T, mode: Mode) -> Self {
+        Self::with_options(filename, mode, Options::new())
+    }
+
+    /// Creates a new `Wrq` that requests the given options.
+    pub fn with_options<T: AsRef<str>>(filename: T, mode: Mode, options: Options) -> Self {
         let filename = filename.as_ref().to_string();
-        Self(Rq { filename, mode })
+        Self(Rq {
+            filename,
+            mode,
+            options,
+        })
     }
 }
 
 impl Packet for Wrq {
     const OPCODE: Opcode = Opcode::Wrq;
+
+    fn check(&self) -> Result<(), EncodeError> {
+        self.0.check()
+    }
 }
 
 impl FromBytes for Wrq {
-    type Error = io::Error;
+    type Error = DecodeError;
 
-    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+    fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Self, DecodeError> {
         let rq = Rq::from_bytes(bytes)?;
 
         Ok(Self(rq))
@@ -0,0 +1,169 @@
+//! Estimation of the retransmission timeout from measured round trip times,
+//! following the algorithm TCP uses (RFC 6298).
+
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use crate::connection::{Settings, MIN_TIMEOUT};
+
+/// Tracks the smoothed round trip time to a peer and derives how long to
+/// wait for it before sending a packet again.
+#[derive(Clone, Copy, Debug)]
+pub struct Rto {
+    /// The smoothed round trip time, once it has been measured.
+    srtt: Option<Duration>,
+
+    /// The round trip time variation.
+    rttvar: Duration,
+
+    /// The current retransmission timeout.
+    timeout: Duration,
+
+    min: Duration,
+    max: Duration,
+
+    /// When the packet being timed was sent, if one is being timed.
+    started: Option<Instant>,
+}
+
+impl Rto {
+    /// Starts from the timeout in `settings`. Unless the settings allow the
+    /// timeout to adapt, it never changes.
+    pub fn new(settings: &Settings) -> Self {
+        let (min, max) = settings
+            .timeout_bounds
+            .unwrap_or((settings.timeout, settings.timeout));
+
+        Self {
+            srtt: None,
+            rttvar: Duration::from_secs(0),
+            timeout: clamp(settings.timeout, min, max),
+            min,
+            max,
+            started: None,
+        }
+    }
+
+    /// How long to wait for the peer before sending the last packet again.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Starts timing a packet that was just sent for the first time.
+    pub fn start(&mut self) {
+        self.started = Some(Instant::now());
+    }
+
+    /// Stops timing once the peer has answered, updating the estimate.
+    pub fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.sample(started.elapsed());
+        }
+    }
+
+    /// Doubles the timeout after it has elapsed without an answer.
+    ///
+    /// The packet being timed is about to be sent again, so its answer can
+    /// no longer be matched to a single transmission (Karn's algorithm)
+    /// and it is not timed any further.
+    pub fn backoff(&mut self) {
+        self.started = None;
+        self.timeout = clamp(self.timeout * 2, self.min, self.max);
+    }
+
+    fn sample(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar = self.rttvar * 3 / 4 + delta / 4;
+                self.srtt = Some(srtt * 7 / 8 + rtt / 8);
+            }
+        }
+
+        let srtt = self.srtt.unwrap_or(rtt);
+        let timeout = srtt + cmp::max(MIN_TIMEOUT, self.rttvar * 4);
+        self.timeout = clamp(timeout, self.min, self.max);
+    }
+}
+
+fn clamp(timeout: Duration, min: Duration, max: Duration) -> Duration {
+    cmp::min(cmp::max(timeout, min), max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn adaptive(min: Duration, max: Duration) -> Rto {
+        Rto::new(&Settings {
+            timeout: ms(1000),
+            timeout_bounds: Some((min, max)),
+            ..Settings::default()
+        })
+    }
+
+    #[test]
+    fn test_fixed_timeout() {
+        let mut rto = Rto::new(&Settings {
+            timeout: ms(500),
+            ..Settings::default()
+        });
+
+        rto.sample(ms(10));
+        assert_eq!(rto.timeout(), ms(500));
+        rto.backoff();
+        assert_eq!(rto.timeout(), ms(500));
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut rto = adaptive(ms(1), ms(60_000));
+        assert_eq!(rto.timeout(), ms(1000));
+
+        // SRTT = 100, RTTVAR = 50
+        rto.sample(ms(100));
+        assert_eq!(rto.timeout(), ms(300));
+
+        // RTTVAR = 3/4 * 50 + 1/4 * 100 = 62.5, SRTT = 7/8 * 100 + 1/8 * 200 = 112.5
+        rto.sample(ms(200));
+        assert_eq!(rto.timeout(), Duration::from_micros(362_500));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut rto = adaptive(ms(50), ms(2000));
+
+        rto.sample(Duration::from_micros(200));
+        assert_eq!(rto.timeout(), ms(50));
+
+        rto.sample(ms(5000));
+        assert_eq!(rto.timeout(), ms(2000));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut rto = adaptive(ms(1), ms(3000));
+        rto.sample(ms(100));
+
+        rto.start();
+        rto.backoff();
+        assert_eq!(rto.timeout(), ms(600));
+        rto.backoff();
+        rto.backoff();
+        assert_eq!(rto.timeout(), ms(2400));
+        rto.backoff();
+        assert_eq!(rto.timeout(), ms(3000));
+
+        // The retransmitted packet is not timed.
+        rto.stop();
+        assert_eq!(rto.timeout(), ms(3000));
+    }
+}
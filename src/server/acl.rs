@@ -0,0 +1,216 @@
+//! Access control by the address a request comes from.
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A block of IP addresses in CIDR notation, such as `10.0.0.0/8` or
+/// `fd00::/8`. A single address stands for a block of just that address.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Creates the block of addresses that share the first `prefix` bits
+    /// with `addr`.
+    pub fn new(addr: IpAddr, prefix: u8) -> io::Result<Self> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > bits {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("prefix length {} is longer than {} bits", prefix, bits),
+            ));
+        }
+
+        Ok(Self {
+            addr: mask(addr, prefix),
+            prefix,
+        })
+    }
+
+    /// Whether `addr` lies within the block. IPv4 addresses mapped into IPv6
+    /// are treated as the IPv4 addresses they stand for.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            IpAddr::V4(_) => addr,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                mask(addr, self.prefix) == self.addr
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(ErrorKind::InvalidInput, format!("invalid CIDR {:?}", s));
+
+        let (addr, prefix) = match s.find('/') {
+            Some(slash) => (&s[..slash], Some(&s[slash + 1..])),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let prefix = match prefix {
+            // Leading `+` signs and the like are not CIDR notation.
+            Some(prefix) if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_digit()) => {
+                return Err(invalid())
+            }
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Cidr::new(addr, prefix)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Clears all but the first `prefix` bits of `addr`.
+fn mask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(v4) => {
+            let bits = u32::from(v4);
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((bits & mask).into())
+        }
+        IpAddr::V6(v6) => {
+            let bits = u128::from(v6);
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((bits & mask).into())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Access {
+    Allow,
+    Deny,
+}
+
+/// Rules for which addresses may read and which may write.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Acl {
+    read: Vec<(Access, Cidr)>,
+    write: Vec<(Access, Cidr)>,
+
+    /// Whether denied requests go unanswered.
+    pub drop_denied: bool,
+}
+
+impl Acl {
+    pub fn allow_read(&mut self, cidr: Cidr) {
+        self.read.push((Access::Allow, cidr));
+    }
+
+    pub fn deny_read(&mut self, cidr: Cidr) {
+        self.read.push((Access::Deny, cidr));
+    }
+
+    pub fn allow_write(&mut self, cidr: Cidr) {
+        self.write.push((Access::Allow, cidr));
+    }
+
+    pub fn deny_write(&mut self, cidr: Cidr) {
+        self.write.push((Access::Deny, cidr));
+    }
+
+    pub fn permits_read(&self, addr: IpAddr) -> bool {
+        permits(&self.read, addr)
+    }
+
+    pub fn permits_write(&self, addr: IpAddr) -> bool {
+        permits(&self.write, addr)
+    }
+}
+
+/// The first rule that matches `addr` decides. Addresses that match no rule
+/// are let in, unless there are rules that allow some addresses in.
+fn permits(rules: &[(Access, Cidr)], addr: IpAddr) -> bool {
+    match rules.iter().find(|(_, cidr)| cidr.contains(addr)) {
+        Some((access, _)) => *access == Access::Allow,
+        None => !rules.iter().any(|(access, _)| *access == Access::Allow),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(s: &str) -> Cidr {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(cidr("10.1.2.3/8"), Cidr::new(ip("10.0.0.0"), 8).unwrap());
+        assert_eq!(cidr("10.1.2.3"), Cidr::new(ip("10.1.2.3"), 32).unwrap());
+        assert_eq!(cidr("0.0.0.0/0").to_string(), "0.0.0.0/0");
+        assert_eq!(cidr("fd00:1::1/16").to_string(), "fd00::/16");
+        assert_eq!(cidr("::1").to_string(), "::1/128");
+
+        for s in &[
+            "",
+            "10.0.0.0/",
+            "10.0.0.0/33",
+            "::/129",
+            "10.0.0/8",
+            "10.0.0.0/+8",
+            "10.0.0.0/8/8",
+            "host/8",
+        ] {
+            assert!(s.parse::<Cidr>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let lan = cidr("192.168.10.0/24");
+        assert!(lan.contains(ip("192.168.10.0")));
+        assert!(lan.contains(ip("192.168.10.255")));
+        assert!(lan.contains(ip("::ffff:192.168.10.7")));
+        assert!(!lan.contains(ip("192.168.11.1")));
+        assert!(!lan.contains(ip("fd00::1")));
+
+        assert!(cidr("0.0.0.0/0").contains(ip("203.0.113.9")));
+        assert!(cidr("fd00::/8").contains(ip("fd12:3456::1")));
+        assert!(!cidr("fd00::/8").contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn test_rules() {
+        let mut acl = Acl::default();
+        assert!(acl.permits_read(ip("203.0.113.9")));
+        assert!(acl.permits_write(ip("203.0.113.9")));
+
+        acl.deny_write(cidr("10.0.0.66"));
+        acl.allow_write(cidr("10.0.0.0/24"));
+        acl.deny_read(cidr("203.0.113.0/24"));
+
+        assert!(acl.permits_write(ip("10.0.0.1")));
+        assert!(!acl.permits_write(ip("10.0.0.66")));
+        assert!(!acl.permits_write(ip("10.0.1.1")));
+
+        assert!(acl.permits_read(ip("10.0.1.1")));
+        assert!(!acl.permits_read(ip("203.0.113.9")));
+    }
+}
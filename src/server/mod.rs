@@ -0,0 +1,646 @@
+//! A TFTP server. Implementors can use this to build a more richly-featured
+//! server application.
+
+use std::cmp;
+use std::io::{self, Read, Result};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::bytes::IntoBytes;
+use crate::connection::{Connection, Settings};
+use crate::connection::{MIN_PORT_NUMBER, MIN_TIMEOUT};
+use crate::negotiate::{self, Limits, Request};
+use crate::netascii::{NetAsciiReader, NetAsciiWriter};
+use crate::packet::*;
+use crate::storage::{FileSystem, Storage, SymlinkPolicy};
+
+mod acl;
+mod policy;
+mod pool;
+mod provider;
+mod remap;
+
+use acl::Acl;
+pub use acl::Cidr;
+pub use policy::OverwritePolicy;
+use policy::Policy;
+pub use pool::BusyPolicy;
+use pool::{Pool, Workers};
+pub use provider::{Content, Provider, ReadRequest};
+use remap::remap;
+pub use remap::RemapRule;
+
+/// A TFTP server that keeps its files in a `Storage`, which is the serve
+/// directory unless another one is given.
+pub struct Server<S = FileSystem> {
+    socket: UdpSocket,
+    acl: Acl,
+    workers: Workers,
+    config: Config<S>,
+}
+
+/// The configuration shared by a `Server` and its `Handler`s.
+struct Config<S> {
+    storage: Arc<S>,
+    provider: Option<Arc<dyn Provider>>,
+    remap: Vec<RemapRule>,
+    policy: Policy,
+    limits: Limits,
+    settings: Settings,
+}
+
+impl<S> Clone for Config<S> {
+    fn clone(&self) -> Self {
+        Self {
+            storage: Arc::clone(&self.storage),
+            provider: self.provider.clone(),
+            remap: self.remap.clone(),
+            policy: self.policy.clone(),
+            limits: self.limits,
+            settings: self.settings,
+        }
+    }
+}
+
+impl Server {
+    /// Creates a server configured to serve files from a given directory on
+    /// a given address.
+    pub fn new<A: ToSocketAddrs, P: AsRef<Path>>(bind_to: A, serve_from: P) -> Result<Self> {
+        Self::with_storage(bind_to, FileSystem::new(serve_from))
+    }
+
+    /// Creates a server configured to serve files from a given directory on
+    /// a given ip_address and a random port.
+    /// On success the chosen port and the new `Server` instance are returned.
+    pub fn random_port<A: AsRef<str>, P: AsRef<Path>>(
+        ip_addr: A,
+        serve_from: P,
+    ) -> Result<(u16, Self)> {
+        let mut rng = rand::thread_rng();
+        let port: u16 = rng.gen_range(MIN_PORT_NUMBER..u16::MAX);
+        let bind_to = format!("{}:{}", ip_addr.as_ref(), port);
+
+        Self::new(bind_to, serve_from).map(|server| (port, server))
+    }
+
+    /// Sets how symbolic links inside the serve directory are treated,
+    /// which is `SymlinkPolicy::WithinRoot` by default.
+    ///
+    /// Requests for paths that the policy does not allow are answered with
+    /// `Code::AccessViolation`.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        let storage = Arc::make_mut(&mut self.config.storage);
+        *storage = storage.clone().symlinks(policy);
+        self
+    }
+
+    /// Sets whether requested names are taken to be Windows paths, with
+    /// backslashes as separators and no regard for case, as many PXE boot
+    /// ROMs send them. They are not by default.
+    pub fn windows_paths(mut self, enabled: bool) -> Self {
+        let storage = Arc::make_mut(&mut self.config.storage);
+        *storage = storage.clone().windows_paths(enabled);
+        self
+    }
+}
+
+impl<S: Storage> Server<S> {
+    /// Creates a server that keeps its files in `storage`, on a given
+    /// address.
+    pub fn with_storage<A: ToSocketAddrs>(bind_to: A, storage: S) -> Result<Self> {
+        let socket = UdpSocket::bind(bind_to)?;
+        Ok(Self {
+            socket,
+            acl: Acl::default(),
+            workers: Workers::default(),
+            config: Config {
+                storage: Arc::new(storage),
+                provider: None,
+                remap: Vec::new(),
+                policy: Policy::default(),
+                limits: Limits::default(),
+                settings: Settings::default(),
+            },
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Caps the block size (RFC 2348) that clients may negotiate.
+    ///
+    /// Clients asking for larger blocks are offered `size` instead. Sizes
+    /// below the 8 byte minimum are raised to it.
+    pub fn max_block_size(mut self, size: u16) -> Self {
+        self.config.limits.max_block_size = cmp::max(size as usize, MIN_BLOCK_SIZE);
+        self
+    }
+
+    /// Caps the window size (RFC 7440) that clients may negotiate, which is
+    /// 64 blocks by default.
+    ///
+    /// Each transfer buffers up to a window's worth of blocks in memory.
+    pub fn max_window_size(mut self, size: u16) -> Self {
+        self.config.limits.max_window_size = cmp::max(size, 1);
+        self
+    }
+
+    /// Rejects write requests that announce, through the `tsize` option
+    /// (RFC 2349), a file larger than `size` bytes.
+    ///
+    /// The request is answered with `Code::DiskFull` before any data is
+    /// transferred.
+    pub fn max_upload_size(mut self, size: u64) -> Self {
+        self.config.limits.max_upload_size = Some(size);
+        self
+    }
+
+    /// Sets how long to wait for a client before sending the last packet
+    /// again, which is 3 seconds by default.
+    ///
+    /// A timeout negotiated by the client (RFC 2349) takes precedence.
+    pub fn retransmit_timeout(mut self, timeout: Duration) -> Self {
+        self.config.settings.timeout = cmp::max(timeout, MIN_TIMEOUT);
+        self
+    }
+
+    /// Lets the timeout adapt to the measured round trip time to each
+    /// client, staying between `min` and `max`.
+    ///
+    /// The timeout starts out as the one set by `retransmit_timeout` and
+    /// doubles each time it elapses (RFC 6298). A timeout negotiated by the
+    /// client keeps the timeout fixed instead.
+    pub fn adaptive_timeout(mut self, min: Duration, max: Duration) -> Self {
+        let min = cmp::max(min, MIN_TIMEOUT);
+        self.config.settings.timeout_bounds = Some((min, cmp::max(min, max)));
+        self
+    }
+
+    /// Generates the files that clients download with `provider`, falling
+    /// back to storage for the requests it leaves alone.
+    pub fn provider<P: Provider + 'static>(mut self, provider: P) -> Self {
+        self.config.provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Adds a rule that rewrites the filenames clients request before they
+    /// are looked up.
+    ///
+    /// Rules apply in the order they were added, before any other rule of
+    /// the server looks at the name.
+    pub fn remap(mut self, rule: RemapRule) -> Self {
+        self.config.remap.push(rule);
+        self
+    }
+
+    /// Sets whether clients may download files, which they may by default.
+    ///
+    /// Read requests are answered with `Code::IllegalOperation` otherwise.
+    pub fn allow_reads(mut self, allow: bool) -> Self {
+        self.config.policy.reads = allow;
+        self
+    }
+
+    /// Sets whether clients may upload files, which they may by default.
+    ///
+    /// Write requests are answered with `Code::IllegalOperation` otherwise.
+    pub fn allow_writes(mut self, allow: bool) -> Self {
+        self.config.policy.writes = allow;
+        self
+    }
+
+    /// Confines uploads to `dir`, a directory relative to the serve
+    /// directory. May be called more than once to allow several directories.
+    ///
+    /// Uploads to anywhere else are answered with `Code::AccessViolation`.
+    pub fn upload_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.config.policy.upload_dirs.push(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Sets what happens when a client uploads a file that already exists,
+    /// which is `OverwritePolicy::Reject` by default.
+    pub fn overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.config.policy.overwrite = policy;
+        self
+    }
+
+    /// Sets what happens when a client uploads a file that already exists
+    /// under `prefix`, a path relative to the serve directory.
+    ///
+    /// When several prefixes match an upload, the longest one applies.
+    pub fn overwrite_in<P: AsRef<Path>>(mut self, prefix: P, policy: OverwritePolicy) -> Self {
+        self.config
+            .policy
+            .overwrite_rules
+            .push((prefix.as_ref().to_owned(), policy));
+        self
+    }
+
+    /// Lets clients in `cidr` download files.
+    ///
+    /// Rules for reads are checked in the order they were added, and the
+    /// first one that matches the client's address decides. Once any client
+    /// is allowed to read, clients that match no rule are denied.
+    pub fn allow_reads_from(mut self, cidr: Cidr) -> Self {
+        self.acl.allow_read(cidr);
+        self
+    }
+
+    /// Stops clients in `cidr` from downloading files.
+    pub fn deny_reads_from(mut self, cidr: Cidr) -> Self {
+        self.acl.deny_read(cidr);
+        self
+    }
+
+    /// Lets clients in `cidr` upload files.
+    ///
+    /// Rules for writes are checked in the order they were added, and the
+    /// first one that matches the client's address decides. Once any client
+    /// is allowed to write, clients that match no rule are denied.
+    pub fn allow_writes_from(mut self, cidr: Cidr) -> Self {
+        self.acl.allow_write(cidr);
+        self
+    }
+
+    /// Stops clients in `cidr` from uploading files.
+    pub fn deny_writes_from(mut self, cidr: Cidr) -> Self {
+        self.acl.deny_write(cidr);
+        self
+    }
+
+    /// Sets whether requests from clients that the address rules deny are
+    /// ignored, rather than answered with `Code::AccessViolation` as they
+    /// are by default.
+    pub fn drop_denied(mut self, drop: bool) -> Self {
+        self.acl.drop_denied = drop;
+        self
+    }
+
+    /// Sets how many times the last packet is sent again before a transfer
+    /// fails with `ErrorKind::TimedOut`, which is 5 by default.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.config.settings.max_retries = retries;
+        self
+    }
+
+    /// Sets how many transfers `run` handles at once, which is 64 by
+    /// default. Each takes up a thread, which is kept for later transfers
+    /// once it is started.
+    pub fn max_transfers(mut self, transfers: usize) -> Self {
+        self.workers.max = cmp::max(transfers, 1);
+        self
+    }
+
+    /// Sets what `run` does with requests that arrive while it is handling
+    /// as many transfers as it may, which is to queue them by default.
+    pub fn when_busy(mut self, policy: BusyPolicy) -> Self {
+        self.workers.busy = policy;
+        self
+    }
+
+    /// Sets how many requests `run` queues while it is busy, which is 64 by
+    /// default. Requests beyond that are rejected as they are with
+    /// `BusyPolicy::Reject`.
+    pub fn max_queued(mut self, requests: usize) -> Self {
+        self.workers.max_queued = requests;
+        self
+    }
+
+    /// Waits for requests and returns a `Handler` instance.
+    ///
+    /// It is intended that implementors will loop on this method and may
+    /// optionally use the decoupled `Handler` instance at a time of their
+    /// choosing to service the request.
+    ///
+    /// This is designed to be friendly to server implementations of all types.
+    /// For example, a server application that employs the use of a thread pool
+    /// can simply send the `Handler` off into the thread pool to be serviced.
+    /// `run` does just that, with a pool of its own.
+    ///
+    /// Requests from clients that the address rules deny fail with
+    /// `ErrorKind::PermissionDenied`.
+    /* TODO: Maybe return option instead? */
+    pub fn serve(&self) -> Result<Handler<S>> {
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let (nbytes, src_addr) = self.socket.recv_from(&mut buf)?;
+        self.accept(&buf[..nbytes], src_addr)?.bind()
+    }
+
+    /// Serves requests on up to `max_transfers` threads, until receiving a
+    /// request fails. Threads are started as they are needed.
+    ///
+    /// Requests that cannot be served are answered with an error packet and
+    /// otherwise ignored, as are requests that clients send again while
+    /// their transfer is queued or in progress. Requests that were queued
+    /// for longer than the server would wait for a client are dropped, as
+    /// the client has given up on them.
+    pub fn run(self) -> Result<()>
+    where
+        S: 'static,
+    {
+        let pool = Pool::new(self.workers, self.socket.try_clone()?);
+        let mut buf = [0; MAX_PACKET_SIZE];
+        loop {
+            let (nbytes, src_addr) = self.socket.recv_from(&mut buf)?;
+            if pool.has(src_addr) {
+                continue;
+            }
+            if let Ok(request) = self.accept(&buf[..nbytes], src_addr) {
+                if !pool.submit(request) {
+                    let error = Packet::error(Code::NotDefined, "server is busy, try again later");
+                    let _ = reject(&self.socket, src_addr, error);
+                }
+            }
+        }
+    }
+
+    /// Checks a request and takes it on, or answers it with an error.
+    fn accept(&self, request: &[u8], src_addr: SocketAddr) -> Result<Accepted<S>> {
+        let direction = match AnyPacket::decode(request) {
+            Ok(AnyPacket::Rrq(rq)) => Direction::Get(rq),
+            Ok(AnyPacket::Wrq(wq)) => Direction::Put(wq),
+            other => {
+                let reason = match other {
+                    Ok(packet) => DecodeError::UnexpectedOpcode(packet.opcode()),
+                    Err(reason) => reason,
+                };
+                let error = Packet::error(
+                    Code::IllegalOperation,
+                    format!("{}: {}", Code::IllegalOperation, reason),
+                );
+                let _ = self.socket.send_to(&error.into_bytes()[..], src_addr);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+            }
+        };
+
+        let permitted = match direction {
+            Direction::Get(_) => self.acl.permits_read(src_addr.ip()),
+            Direction::Put(_) => self.acl.permits_write(src_addr.ip()),
+        };
+        if !permitted {
+            let message = format!("requests from {} are not allowed", src_addr.ip());
+            if !self.acl.drop_denied {
+                let error = Packet::error(Code::AccessViolation, &message);
+                let _ = self.socket.send_to(&error.into_bytes()[..], src_addr);
+            }
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+        }
+
+        Ok(Accepted {
+            local: self.socket.local_addr()?.ip(),
+            client: src_addr,
+            direction,
+            config: self.config.clone(),
+        })
+    }
+}
+
+/// A request that has been taken on, but does not have a socket of its own
+/// yet.
+struct Accepted<S> {
+    local: IpAddr,
+    client: SocketAddr,
+    direction: Direction,
+    config: Config<S>,
+}
+
+impl<S: Storage> Accepted<S> {
+    /// Opens a socket on a random port for the transfer.
+    fn bind(self) -> Result<Handler<S>> {
+        let mut rng = rand::thread_rng();
+        let port: u16 = rng.gen_range(1001..u16::MAX);
+        let bind_to = format!("{}:{}", self.local, port);
+
+        Handler::new(bind_to, self.client, self.direction, self.config)
+    }
+
+    /// How long the client keeps sending its request before it gives up,
+    /// going by the retransmission settings of the server.
+    fn patience(&self) -> Duration {
+        let settings = &self.config.settings;
+        settings.timeout * (settings.max_retries + 1)
+    }
+}
+
+enum Direction {
+    Get(Packet<Rrq>),
+    Put(Packet<Wrq>),
+}
+
+/// Handles a request from a single TFTP client.
+pub struct Handler<S = FileSystem> {
+    socket: UdpSocket,
+    client: SocketAddr,
+    direction: Direction,
+    config: Config<S>,
+}
+
+impl<S: Storage> Handler<S> {
+    fn new<A: ToSocketAddrs>(
+        bind: A,
+        client: SocketAddr,
+        direction: Direction,
+        config: Config<S>,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind(bind)?;
+
+        Ok(Handler {
+            socket,
+            client,
+            direction,
+            config,
+        })
+    }
+
+    /// Completes the handshake with the client and services the request.
+    pub fn handle(self) -> Result<()> {
+        match self.direction {
+            Direction::Get(_) => self.get(),
+            Direction::Put(_) => self.put(),
+        }
+    }
+
+    fn get(self) -> Result<()> {
+        if let Direction::Get(rrq) = self.direction {
+            if !self.config.policy.reads {
+                let error = Packet::error(Code::IllegalOperation, "downloads are disabled");
+                return Err(reject(&self.socket, self.client, error));
+            }
+
+            let mut rrq = rrq.body.0;
+            rrq.filename = match remap(&self.config.remap, &rrq.filename, self.client.ip(), false) {
+                Some(filename) => filename,
+                None => return Err(reject(&self.socket, self.client, denied_by_remap())),
+            };
+
+            let provided = match &self.config.provider {
+                Some(provider) => {
+                    let (negotiated, _) = negotiate::accept(
+                        &rrq.options,
+                        Request::Read(None),
+                        &self.config.limits,
+                        self.config.settings,
+                    );
+                    provider.provide(&ReadRequest {
+                        filename: &rrq.filename,
+                        client: self.client,
+                        mode: rrq.mode,
+                        options: &rrq.options,
+                        negotiated: &negotiated,
+                    })
+                }
+                None => Ok(None),
+            };
+            let opened = match provided {
+                Ok(Some(content)) => Ok((content.reader, content.size)),
+                Ok(None) => self
+                    .config
+                    .storage
+                    .open(&rrq.filename)
+                    .map(|(f, size)| (Box::new(f) as Box<dyn Read + Send>, size)),
+                Err(e) => Err(e),
+            };
+            let (f, size) = match opened {
+                Ok(opened) => opened,
+                Err(e) => return Err(reject(&self.socket, self.client, storage_error(e))),
+            };
+
+            // The size of a netascii file on the wire is not known without
+            // translating all of it first.
+            let size = match rrq.mode {
+                Mode::NetAscii => None,
+                _ => size,
+            };
+            let (accepted, settings) = negotiate::accept(
+                &rrq.options,
+                Request::Read(size),
+                &self.config.limits,
+                self.config.settings,
+            );
+
+            let mut conn = Connection::new(self.socket, self.client, settings)?;
+            if !accepted.is_empty() {
+                // The client must acknowledge our OACK with block 0 before
+                // we start sending data.
+                conn.handshake(Packet::oack(accepted))?;
+            }
+            match rrq.mode {
+                Mode::NetAscii => conn.put(NetAsciiReader::new(f))?,
+                _ => conn.put(f)?,
+            }
+            Ok(())
+        } else {
+            panic!("handler direction is wrong");
+        }
+    }
+
+    fn put(self) -> Result<()> {
+        if let Direction::Put(wrq) = self.direction {
+            if !self.config.policy.writes {
+                let error = Packet::error(Code::IllegalOperation, "uploads are disabled");
+                return Err(reject(&self.socket, self.client, error));
+            }
+
+            let mut wrq = wrq.body.0;
+            wrq.filename = match remap(&self.config.remap, &wrq.filename, self.client.ip(), true) {
+                Some(filename) => filename,
+                None => return Err(reject(&self.socket, self.client, denied_by_remap())),
+            };
+
+            // The name is resolved the way the storage reads it before the
+            // policy sees it. Otherwise, with `windows_paths` set, a name like
+            // `uploads\..\x` could pass the prefix check and then resolve
+            // outside `upload_dirs`.
+            let storage = self.config.storage;
+            wrq.filename = match storage.canonical_name(&wrq.filename) {
+                Ok(filename) => filename,
+                Err(e) => return Err(reject(&self.socket, self.client, storage_error(e))),
+            };
+
+            if !self.config.policy.permits_upload(&wrq.filename) {
+                let error = Packet::error(
+                    Code::AccessViolation,
+                    "uploads are not allowed into this directory",
+                );
+                return Err(reject(&self.socket, self.client, error));
+            }
+
+            let (accepted, settings) = negotiate::accept(
+                &wrq.options,
+                Request::Write,
+                &self.config.limits,
+                self.config.settings,
+            );
+
+            let max_upload_size = self.config.limits.max_upload_size;
+            if let (Some(size), Some(max)) = (settings.transfer_size, max_upload_size) {
+                if size > max {
+                    let error = Packet::error(
+                        Code::DiskFull,
+                        format!("file size {} exceeds the limit of {} bytes", size, max),
+                    );
+                    return Err(reject(&self.socket, self.client, error));
+                }
+            }
+
+            let overwrite = self.config.policy.overwrite_policy(&wrq.filename);
+            let upload = match storage.create(&wrq.filename, overwrite) {
+                Ok(upload) => upload,
+                Err(e) => return Err(reject(&self.socket, self.client, storage_error(e))),
+            };
+
+            // An OACK takes the place of the ACK for block 0.
+            let reply = if accepted.is_empty() {
+                Packet::ack(Block::new(0)).into_bytes()
+            } else {
+                Packet::oack(accepted).into_bytes()
+            };
+
+            let mut conn = Connection::new(self.socket, self.client, settings)?;
+            conn.send(reply)?;
+            // The upload only appears under its name once the last block has
+            // arrived.
+            match wrq.mode {
+                Mode::NetAscii => {
+                    conn.get_then(NetAsciiWriter::new(upload), |writer| {
+                        storage.commit(writer.finish()?)
+                    })?;
+                }
+                _ => {
+                    conn.get_then(upload, |upload| storage.commit(upload))?;
+                }
+            }
+            Ok(())
+        } else {
+            panic!("handler direction is wrong");
+        }
+    }
+}
+
+/// Describes a failure of the storage to the client. Messages from the
+/// operating system are left out, as they may reveal more than the client
+/// should know.
+fn storage_error(e: io::Error) -> Packet<Error> {
+    let code = Code::from(e.kind());
+    match e.get_ref() {
+        Some(inner) => Packet::error(code, inner.to_string()),
+        None => Packet::error(code, code.to_string()),
+    }
+}
+
+fn denied_by_remap() -> Packet<Error> {
+    Packet::error(Code::AccessViolation, "access to this file is denied")
+}
+
+/// Sends `error` to the client and returns it for the handler to fail with.
+fn reject(socket: &UdpSocket, client: SocketAddr, error: Packet<Error>) -> io::Error {
+    let _ = socket.send_to(&error.clone().into_bytes()[..], client);
+    io::Error::from(error)
+}
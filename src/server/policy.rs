@@ -0,0 +1,146 @@
+//! Decides which requests a server is willing to serve at all, and what
+//! happens to files that uploads would replace.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::storage::normalize;
+
+/// Which directions of transfer a server allows, and where uploads may go.
+#[derive(Clone, Debug)]
+pub(crate) struct Policy {
+    pub reads: bool,
+    pub writes: bool,
+
+    /// The directories, relative to the serve directory, that uploads are
+    /// confined to. Uploads may go anywhere when this is empty.
+    pub upload_dirs: Vec<PathBuf>,
+
+    /// What to do when an upload would replace an existing file.
+    pub overwrite: OverwritePolicy,
+
+    /// Overrides `overwrite` for uploads under a path prefix.
+    pub overwrite_rules: Vec<(PathBuf, OverwritePolicy)>,
+}
+
+/// What a server does when a client uploads a file that already exists.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum OverwritePolicy {
+    /// Refuses the upload with `Code::FileAlreadyExists`.
+    #[default]
+    Reject,
+
+    /// Replaces the existing file.
+    Overwrite,
+
+    /// Keeps the existing file and stores the upload next to it, with `.1`,
+    /// `.2` and so on appended to its name.
+    KeepBoth,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            reads: true,
+            writes: true,
+            upload_dirs: Vec::new(),
+            overwrite: OverwritePolicy::default(),
+            overwrite_rules: Vec::new(),
+        }
+    }
+}
+
+impl Policy {
+    /// Whether a file may be uploaded under the requested `name`.
+    pub fn permits_upload(&self, name: &str) -> bool {
+        if self.upload_dirs.is_empty() {
+            return true;
+        }
+
+        let name = match normalize(name) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        self.upload_dirs
+            .iter()
+            .any(|dir| name.starts_with(relative(dir)) && name != relative(dir))
+    }
+
+    /// The overwrite policy for an upload under the requested `name`, taken
+    /// from the rule with the longest matching prefix.
+    pub fn overwrite_policy(&self, name: &str) -> OverwritePolicy {
+        let name = match normalize(name) {
+            Ok(name) => name,
+            Err(_) => return self.overwrite,
+        };
+        self.overwrite_rules
+            .iter()
+            .map(|(prefix, policy)| (relative(prefix), *policy))
+            .filter(|(prefix, _)| name.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.components().count())
+            .map_or(self.overwrite, |(_, policy)| policy)
+    }
+}
+
+/// Strips the leading `/` or `.` that a directory may be given with.
+fn relative(dir: &Path) -> PathBuf {
+    dir.components()
+        .filter(|component| !matches!(component, Component::RootDir | Component::CurDir))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permits_upload_anywhere_by_default() {
+        let policy = Policy::default();
+        assert!(policy.permits_upload("file"));
+        assert!(policy.permits_upload("dir/file"));
+    }
+
+    #[test]
+    fn test_permits_upload_into_upload_dirs() {
+        let policy = Policy {
+            upload_dirs: vec![PathBuf::from("backups"), PathBuf::from("/incoming/cfg")],
+            ..Policy::default()
+        };
+
+        assert!(policy.permits_upload("backups/router.cfg"));
+        assert!(policy.permits_upload("/backups/site/router.cfg"));
+        assert!(policy.permits_upload("incoming/cfg/switch.cfg"));
+        assert!(policy.permits_upload("other/../backups/router.cfg"));
+
+        assert!(!policy.permits_upload("router.cfg"));
+        assert!(!policy.permits_upload("backups"));
+        assert!(!policy.permits_upload("backups-old/router.cfg"));
+        assert!(!policy.permits_upload("incoming/switch.cfg"));
+        assert!(!policy.permits_upload("backups/../router.cfg"));
+    }
+
+    #[test]
+    fn test_overwrite_policy_by_prefix() {
+        let policy = Policy {
+            overwrite: OverwritePolicy::Overwrite,
+            overwrite_rules: vec![
+                (PathBuf::from("/backups"), OverwritePolicy::KeepBoth),
+                (PathBuf::from("backups/golden"), OverwritePolicy::Reject),
+            ],
+            ..Policy::default()
+        };
+
+        assert_eq!(policy.overwrite_policy("a.cfg"), OverwritePolicy::Overwrite);
+        assert_eq!(
+            policy.overwrite_policy("backups/a.cfg"),
+            OverwritePolicy::KeepBoth
+        );
+        assert_eq!(
+            policy.overwrite_policy("backups/golden/a.cfg"),
+            OverwritePolicy::Reject
+        );
+        assert_eq!(
+            policy.overwrite_policy("backups-old/a.cfg"),
+            OverwritePolicy::Overwrite
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Runs the transfers of a server on a bounded number of threads.
+
+use std::collections::{HashSet, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use super::{reject, Accepted};
+use crate::packet::{Code, Packet};
+use crate::storage::Storage;
+
+/// What `Server::run` does with requests that arrive while it is already
+/// running as many transfers as it may.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BusyPolicy {
+    /// Holds on to requests until a transfer finishes, up to
+    /// `Server::max_queued` of them.
+    #[default]
+    Queue,
+
+    /// Answers requests with `Code::NotDefined`, saying that the server is
+    /// busy.
+    Reject,
+}
+
+/// How many transfers a server runs at once, and what happens to the
+/// requests beyond that.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Workers {
+    pub max: usize,
+    pub busy: BusyPolicy,
+    pub max_queued: usize,
+}
+
+impl Default for Workers {
+    fn default() -> Self {
+        Self {
+            max: 64,
+            busy: BusyPolicy::default(),
+            max_queued: 64,
+        }
+    }
+}
+
+/// A request waiting for a worker.
+struct Job<S> {
+    request: Accepted<S>,
+    queued: Instant,
+}
+
+/// What the pool and its workers share.
+struct State<S> {
+    queue: VecDeque<Job<S>>,
+    /// Clients that are queued or being served.
+    clients: HashSet<SocketAddr>,
+    /// How many workers have been started, and how many of them are waiting
+    /// for a job.
+    started: usize,
+    idle: usize,
+    closed: bool,
+}
+
+struct Shared<S> {
+    state: Mutex<State<S>>,
+    wake: Condvar,
+    /// The socket the server receives requests on.
+    listener: UdpSocket,
+}
+
+/// Threads that take requests off a shared queue. Threads are started as
+/// requests come in, up to the limit, and kept for later requests.
+///
+/// Clients that are queued or being served are remembered by address, so
+/// that the requests they send again while they wait are not served twice.
+pub(crate) struct Pool<S> {
+    shared: Arc<Shared<S>>,
+    workers: Workers,
+}
+
+impl<S: Storage + 'static> Pool<S> {
+    pub fn new(workers: Workers, listener: UdpSocket) -> Self {
+        let state = State {
+            queue: VecDeque::new(),
+            clients: HashSet::new(),
+            started: 0,
+            idle: 0,
+            closed: false,
+        };
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                wake: Condvar::new(),
+                listener,
+            }),
+            workers,
+        }
+    }
+
+    /// Whether `client` already has a transfer queued or in progress.
+    pub fn has(&self, client: SocketAddr) -> bool {
+        lock(&self.shared.state).clients.contains(&client)
+    }
+
+    /// Hands `request` to a worker. Returns `false` if the pool cannot take
+    /// it on.
+    pub fn submit(&self, request: Accepted<S>) -> bool {
+        let mut state = lock(&self.shared.state);
+        let capacity = match self.workers.busy {
+            BusyPolicy::Queue => self.workers.max + self.workers.max_queued,
+            BusyPolicy::Reject => self.workers.max,
+        };
+        if state.clients.len() >= capacity {
+            return false;
+        }
+
+        // Jobs already queued are spoken for by the idle workers first.
+        if state.idle <= state.queue.len() && state.started < self.workers.max {
+            let shared = Arc::clone(&self.shared);
+            thread::spawn(move || work(&shared));
+            state.started += 1;
+            state.idle += 1;
+        }
+
+        state.clients.insert(request.client);
+        state.queue.push_back(Job {
+            request,
+            queued: Instant::now(),
+        });
+        self.shared.wake.notify_one();
+        true
+    }
+}
+
+impl<S> Drop for Pool<S> {
+    fn drop(&mut self) {
+        lock(&self.shared.state).closed = true;
+        self.shared.wake.notify_all();
+    }
+}
+
+fn work<S: Storage>(shared: &Shared<S>) {
+    loop {
+        let job = {
+            let mut state = lock(&shared.state);
+            loop {
+                if let Some(job) = state.queue.pop_front() {
+                    state.idle -= 1;
+                    break job;
+                }
+                if state.closed {
+                    return;
+                }
+                state = shared.wake.wait(state).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        let client = job.request.client;
+        // A client that waited longer than it keeps trying has given up on
+        // the transfer, so it would only be sent packets it ignores.
+        if job.queued.elapsed() <= job.request.patience() {
+            match job.request.bind() {
+                // A provider that panics should not take the worker down
+                // with it.
+                Ok(handler) => {
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler.handle()));
+                }
+                // Without a socket of its own, the transfer can only be
+                // refused from the one the request arrived on.
+                Err(e) => {
+                    let message = format!("cannot start the transfer: {}", e);
+                    let _ = reject(
+                        &shared.listener,
+                        client,
+                        Packet::error(Code::NotDefined, message),
+                    );
+                }
+            }
+        }
+
+        let mut state = lock(&shared.state);
+        state.clients.remove(&client);
+        state.idle += 1;
+    }
+}
+
+// Nothing is left half done while these locks are held, so a poisoned lock
+// is still safe to use.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
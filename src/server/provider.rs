@@ -0,0 +1,95 @@
+//! Content that is generated for each read request, rather than kept in
+//! storage.
+
+use std::fmt;
+use std::io::{Cursor, Read, Result};
+use std::net::SocketAddr;
+
+use crate::packet::{Mode, Options};
+
+/// A read request, as seen by a `Provider`.
+#[derive(Debug)]
+pub struct ReadRequest<'a> {
+    /// The name of the file the client asked for.
+    pub filename: &'a str,
+
+    /// The address of the client.
+    pub client: SocketAddr,
+
+    /// The mode the file is to be transferred in.
+    pub mode: Mode,
+
+    /// The options the client asked for.
+    pub options: &'a Options,
+
+    /// The options the server is going to acknowledge, except for `tsize`,
+    /// which depends on the content.
+    pub negotiated: &'a Options,
+}
+
+/// The contents of a file that a `Provider` generated.
+pub struct Content {
+    pub(crate) reader: Box<dyn Read + Send>,
+    pub(crate) size: Option<u64>,
+}
+
+impl Content {
+    /// Content that is read from `reader`, whose size is not known ahead of
+    /// time. Clients that ask for the `tsize` option do not get it.
+    pub fn new<R: Read + Send + 'static>(reader: R) -> Self {
+        Self {
+            reader: Box::new(reader),
+            size: None,
+        }
+    }
+
+    /// Content of `size` bytes that is read from `reader`.
+    pub fn with_size<R: Read + Send + 'static>(reader: R, size: u64) -> Self {
+        Self {
+            reader: Box::new(reader),
+            size: Some(size),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(data: Vec<u8>) -> Self {
+        let size = data.len() as u64;
+        Self::with_size(Cursor::new(data), size)
+    }
+}
+
+impl From<String> for Content {
+    fn from(data: String) -> Self {
+        data.into_bytes().into()
+    }
+}
+
+impl fmt::Debug for Content {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Content").field("size", &self.size).finish()
+    }
+}
+
+/// Generates the files that clients download.
+///
+/// A server asks its provider first. If the provider has nothing for a
+/// request, the file is looked up in storage instead. Errors are reported to
+/// the client the same way storage errors are, so a provider that fails with
+/// `ErrorKind::NotFound` hides a file in storage.
+///
+/// Closures that take a `&ReadRequest` are providers as well.
+pub trait Provider: Send + Sync {
+    /// Returns the content for `request`, or `None` to leave the request to
+    /// storage.
+    fn provide(&self, request: &ReadRequest<'_>) -> Result<Option<Content>>;
+}
+
+impl<F> Provider for F
+where
+    F: Fn(&ReadRequest<'_>) -> Result<Option<Content>> + Send + Sync,
+{
+    fn provide(&self, request: &ReadRequest<'_>) -> Result<Option<Content>> {
+        self(request)
+    }
+}
@@ -0,0 +1,209 @@
+//! Rewrites the filenames that clients request before they are looked up,
+//! much like the map files of tftp-hpa.
+
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+
+use regex::Regex;
+
+use super::acl::Cidr;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Only {
+    Reads,
+    Writes,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Action {
+    Rewrite,
+    Deny,
+}
+
+/// A rule that rewrites the filenames that clients request.
+///
+/// A server applies its rules in the order they were added, each to the
+/// name that the rules before it produced. Rules are matched anywhere in the
+/// name unless their pattern is anchored, and `(?i)` makes them ignore case.
+#[derive(Clone, Debug)]
+pub struct RemapRule {
+    regex: Regex,
+    replacement: String,
+    action: Action,
+    global: bool,
+    stop: bool,
+    only: Option<Only>,
+    clients: Vec<Cidr>,
+}
+
+impl RemapRule {
+    /// Replaces the first match of `pattern` with `replacement`, in which
+    /// `$1` or `$name` stand for the groups of the match.
+    ///
+    /// Fails with `ErrorKind::InvalidInput` if `pattern` is not a valid
+    /// regular expression.
+    pub fn rewrite(pattern: &str, replacement: &str) -> io::Result<Self> {
+        let regex = Regex::new(pattern).map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            regex,
+            replacement: replacement.to_string(),
+            action: Action::Rewrite,
+            global: false,
+            stop: false,
+            only: None,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Denies requests for names that match `pattern`. They are answered
+    /// with `Code::AccessViolation`.
+    pub fn deny(pattern: &str) -> io::Result<Self> {
+        let mut rule = Self::rewrite(pattern, "")?;
+        rule.action = Action::Deny;
+        Ok(rule)
+    }
+
+    /// Replaces every match rather than just the first.
+    pub fn global(mut self) -> Self {
+        self.global = true;
+        self
+    }
+
+    /// Leaves the name alone after this rule, if it matched.
+    pub fn stop(mut self) -> Self {
+        self.stop = true;
+        self
+    }
+
+    /// Only applies the rule to read requests.
+    pub fn reads_only(mut self) -> Self {
+        self.only = Some(Only::Reads);
+        self
+    }
+
+    /// Only applies the rule to write requests.
+    pub fn writes_only(mut self) -> Self {
+        self.only = Some(Only::Writes);
+        self
+    }
+
+    /// Only applies the rule to clients in `cidr`. May be called more than
+    /// once to apply it to clients in any of several blocks.
+    pub fn clients_in(mut self, cidr: Cidr) -> Self {
+        self.clients.push(cidr);
+        self
+    }
+
+    fn applies(&self, client: IpAddr, write: bool) -> bool {
+        let direction = match self.only {
+            Some(Only::Reads) => !write,
+            Some(Only::Writes) => write,
+            None => true,
+        };
+        direction && (self.clients.is_empty() || self.clients.iter().any(|c| c.contains(client)))
+    }
+}
+
+/// Applies `rules` to the name a client requested, returning the name to
+/// look up, or `None` if a rule denies the request.
+pub(crate) fn remap(
+    rules: &[RemapRule],
+    name: &str,
+    client: IpAddr,
+    write: bool,
+) -> Option<String> {
+    let mut name = name.to_string();
+    for rule in rules.iter().filter(|rule| rule.applies(client, write)) {
+        if !rule.regex.is_match(&name) {
+            continue;
+        }
+        if rule.action == Action::Deny {
+            return None;
+        }
+
+        let replacement = rule.replacement.as_str();
+        name = if rule.global {
+            rule.regex.replace_all(&name, replacement).into_owned()
+        } else {
+            rule.regex.replace(&name, replacement).into_owned()
+        };
+        if rule.stop {
+            break;
+        }
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 7));
+
+    fn rewrite(pattern: &str, replacement: &str) -> RemapRule {
+        RemapRule::rewrite(pattern, replacement).unwrap()
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rules = vec![
+            rewrite(r"^/", ""),
+            rewrite(r"^(?i)efi/boot/(.*)$", "grub/$1"),
+            rewrite(r"\\", "/").global(),
+        ];
+
+        assert_eq!(
+            remap(&rules, "/EFI/BOOT/grubx64.efi", CLIENT, false).unwrap(),
+            "grub/grubx64.efi"
+        );
+        assert_eq!(
+            remap(&rules, r"boot\x86\pxelinux.0", CLIENT, false).unwrap(),
+            "boot/x86/pxelinux.0"
+        );
+        assert_eq!(remap(&rules, "other", CLIENT, false).unwrap(), "other");
+    }
+
+    #[test]
+    fn test_stop() {
+        let rules = vec![rewrite("^a$", "b").stop(), rewrite("^b$", "c")];
+        assert_eq!(remap(&rules, "a", CLIENT, false).unwrap(), "b");
+        assert_eq!(remap(&rules, "b", CLIENT, false).unwrap(), "c");
+    }
+
+    #[test]
+    fn test_deny() {
+        let rules = vec![
+            rewrite("^old/", "new/").stop(),
+            RemapRule::deny(r"\.\.").unwrap(),
+        ];
+        assert_eq!(remap(&rules, "old/..", CLIENT, false).unwrap(), "new/..");
+        assert_eq!(remap(&rules, "a/../b", CLIENT, false), None);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let lab: Cidr = "10.0.0.0/24".parse().unwrap();
+        let rules = vec![
+            rewrite("^config$", "lab.cfg").clients_in(lab).stop(),
+            rewrite("^config$", "default.cfg").reads_only(),
+            rewrite("^config$", "uploads/config").writes_only(),
+        ];
+
+        let elsewhere = "192.0.2.1".parse().unwrap();
+        assert_eq!(remap(&rules, "config", CLIENT, false).unwrap(), "lab.cfg");
+        assert_eq!(
+            remap(&rules, "config", elsewhere, false).unwrap(),
+            "default.cfg"
+        );
+        assert_eq!(
+            remap(&rules, "config", elsewhere, true).unwrap(),
+            "uploads/config"
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let error = RemapRule::rewrite("(", "").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,177 @@
+//! Looks up names in a directory tree regardless of their case, the way
+//! clients that were written for Windows servers expect.
+
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+/// How coarse the modification times of directories may be. A listing taken
+/// within this long of a change may have missed part of it.
+const TIMESTAMP_GRANULARITY: Duration = Duration::from_secs(2);
+
+/// The entries of a directory by their lowercased names.
+#[derive(Debug)]
+struct Listing {
+    modified: SystemTime,
+    scanned: SystemTime,
+    entries: HashMap<String, OsString>,
+}
+
+impl Listing {
+    /// Whether the listing still matches a directory last modified at
+    /// `modified`.
+    fn is_current(&self, modified: SystemTime) -> bool {
+        self.modified == modified
+            && self
+                .scanned
+                .duration_since(modified)
+                .is_ok_and(|age| age > TIMESTAMP_GRANULARITY)
+    }
+}
+
+/// Remembers the entries of the directories it has looked names up in, so
+/// that a lookup costs a `stat` of the directory rather than a scan of it.
+///
+/// A directory is scanned again once its modification time changes.
+#[derive(Debug, Default)]
+pub(crate) struct CaseCache {
+    listings: Mutex<HashMap<PathBuf, Listing>>,
+}
+
+impl CaseCache {
+    /// Turns `relative` into the path of the entries under `root` that
+    /// match it regardless of case.
+    ///
+    /// Components that match an entry exactly are kept as they are. The
+    /// components from the first one that matches nothing onwards are kept
+    /// as requested, so that uploads land in existing directories.
+    pub fn resolve(&self, root: &Path, relative: &Path) -> Result<PathBuf> {
+        let mut resolved = PathBuf::new();
+        let mut components = relative.components();
+        for component in components.by_ref() {
+            let name = component.as_os_str();
+            let dir = root.join(&resolved);
+            if fs::symlink_metadata(dir.join(name)).is_ok() {
+                resolved.push(name);
+                continue;
+            }
+            match self.find(&dir, name)? {
+                Some(actual) => resolved.push(actual),
+                None => {
+                    resolved.push(name);
+                    break;
+                }
+            }
+        }
+        resolved.extend(components);
+        Ok(resolved)
+    }
+
+    /// Returns the name of the entry in `dir` that matches `name` regardless
+    /// of case. Where several do, the first in order wins.
+    fn find(&self, dir: &Path, name: &OsStr) -> Result<Option<OsString>> {
+        let modified = match fs::metadata(dir).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let key = name.to_string_lossy().to_lowercase();
+
+        let mut listings = self.lock();
+        if let Some(listing) = listings.get(dir).filter(|l| l.is_current(modified)) {
+            return Ok(listing.entries.get(&key).cloned());
+        }
+
+        let scanned = SystemTime::now();
+        let mut names = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort();
+        let mut entries = HashMap::new();
+        for name in names {
+            entries
+                .entry(name.to_string_lossy().to_lowercase())
+                .or_insert(name);
+        }
+
+        let found = entries.get(&key).cloned();
+        listings.insert(
+            dir.to_owned(),
+            Listing {
+                modified,
+                scanned,
+                entries,
+            },
+        );
+        Ok(found)
+    }
+
+    // A panic cannot leave a listing half built, so a poisoned lock is still
+    // safe to use.
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Listing>> {
+        self.listings.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_ignores_case() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("Boot/PXE")).unwrap();
+        fs::write(root.path().join("Boot/PXE/wdsnbp.com"), b"").unwrap();
+        let cache = CaseCache::default();
+
+        let resolve = |name: &str| cache.resolve(root.path(), Path::new(name)).unwrap();
+        assert_eq!(
+            resolve("boot/pxe/WDSNBP.COM"),
+            Path::new("Boot/PXE/wdsnbp.com")
+        );
+        assert_eq!(
+            resolve("Boot/PXE/wdsnbp.com"),
+            Path::new("Boot/PXE/wdsnbp.com")
+        );
+        assert_eq!(resolve("BOOT/new/File"), Path::new("Boot/new/File"));
+        assert_eq!(resolve("missing/Boot"), Path::new("missing/Boot"));
+    }
+
+    #[test]
+    fn test_resolve_prefers_exact_match() {
+        let root = tempfile::tempdir().unwrap();
+        for name in &["Readme", "README", "notes"] {
+            fs::write(root.path().join(name), b"").unwrap();
+        }
+        let cache = CaseCache::default();
+
+        let resolve = |name: &str| cache.resolve(root.path(), Path::new(name)).unwrap();
+        assert_eq!(resolve("Readme"), Path::new("Readme"));
+        assert_eq!(resolve("notes"), Path::new("notes"));
+        if !root.path().join("NOTES").exists() {
+            assert_eq!(resolve("readme"), Path::new("README"));
+        }
+    }
+
+    #[test]
+    fn test_listing_expires_when_directory_changes() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let listing = Listing {
+            modified,
+            scanned: modified + Duration::from_secs(60),
+            entries: HashMap::new(),
+        };
+        assert!(listing.is_current(modified));
+        assert!(!listing.is_current(modified + Duration::from_secs(1)));
+
+        let fresh = Listing {
+            scanned: modified,
+            ..listing
+        };
+        assert!(!fresh.is_current(modified));
+    }
+}
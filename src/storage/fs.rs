@@ -0,0 +1,116 @@
+//! Serves files from a directory.
+
+use std::fs::File;
+use std::io::Result;
+use std::path::Path;
+
+use super::resolve::{Resolver, SymlinkPolicy};
+use super::upload::FileUpload;
+use super::Storage;
+use crate::OverwritePolicy;
+
+/// Keeps files in a directory on the local file system.
+///
+/// Clients cannot reach files outside of the directory. Uploads only appear
+/// under their name once they are complete.
+#[derive(Clone, Debug)]
+pub struct FileSystem {
+    resolver: Resolver,
+}
+
+impl FileSystem {
+    /// Creates a storage for the files in `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            resolver: Resolver::new(root),
+        }
+    }
+
+    /// Sets how symbolic links inside the directory are treated, which is
+    /// `SymlinkPolicy::WithinRoot` by default.
+    ///
+    /// Requests for paths that the policy does not allow fail with
+    /// `ErrorKind::PermissionDenied`.
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.resolver.set_symlinks(policy);
+        self
+    }
+
+    /// Sets whether requested names are taken to be Windows paths, which
+    /// they are not by default.
+    ///
+    /// Windows paths use backslashes as separators as well as slashes, and
+    /// ignore case, so that `\\Boot\\PXE\\WDSNBP.COM` finds
+    /// `boot/pxe/wdsnbp.com`. Directory listings are cached between requests
+    /// and read again once the directory changes.
+    pub fn windows_paths(mut self, enabled: bool) -> Self {
+        self.resolver.set_windows_paths(enabled);
+        self
+    }
+}
+
+impl Storage for FileSystem {
+    type Reader = File;
+    type Writer = FileUpload;
+
+    fn open(&self, name: &str) -> Result<(File, Option<u64>)> {
+        let file = File::open(self.resolver.resolve(name)?)?;
+        let size = file.metadata().ok().map(|metadata| metadata.len());
+        Ok((file, size))
+    }
+
+    fn create(&self, name: &str, overwrite: OverwritePolicy) -> Result<FileUpload> {
+        FileUpload::new(self.resolver.resolve(name)?, overwrite)
+    }
+
+    fn commit(&self, upload: FileUpload) -> Result<()> {
+        upload.commit().map(|_| ())
+    }
+
+    fn canonical_name(&self, name: &str) -> Result<String> {
+        self.resolver.canonical_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{ErrorKind, Read, Write};
+
+    #[test]
+    fn test_open() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("file"), b"data").unwrap();
+        let storage = FileSystem::new(root.path());
+
+        let (mut file, size) = storage.open("/file").unwrap();
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        assert_eq!(&data[..], b"data");
+        assert_eq!(size, Some(4));
+
+        let error = storage.open("missing").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let error = storage.open("../file").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_create() {
+        let root = tempfile::tempdir().unwrap();
+        let storage = FileSystem::new(root.path());
+
+        let mut upload = storage.create("file", OverwritePolicy::Reject).unwrap();
+        upload.write_all(b"data").unwrap();
+        assert!(!root.path().join("file").exists());
+        storage.commit(upload).unwrap();
+        assert_eq!(std::fs::read(root.path().join("file")).unwrap(), b"data");
+
+        let error = storage.create("file", OverwritePolicy::Reject).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        let error = storage
+            .create("../file", OverwritePolicy::Reject)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+}
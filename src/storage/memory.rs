@@ -0,0 +1,233 @@
+//! Serves files from memory.
+
+use std::collections::HashMap;
+use std::io::{Cursor, ErrorKind, Result, Write};
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::resolve::normalize;
+use super::Storage;
+use crate::OverwritePolicy;
+
+/// How many suffixed names `OverwritePolicy::KeepBoth` tries.
+const MAX_VERSIONS: u32 = 10_000;
+
+/// Keeps files in a map from names to contents.
+///
+/// Clones share the same files, so an application can keep one to add
+/// files while a server serves them, or to look at what clients uploaded.
+/// Names are normalized the way a `FileSystem` would, so `/boot.cfg` and
+/// `boot.cfg` are the same file.
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    files: Arc<RwLock<HashMap<String, Arc<[u8]>>>>,
+}
+
+impl Memory {
+    /// Creates a storage without any files.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, returning the contents it replaces.
+    pub fn insert<D: Into<Vec<u8>>>(&self, name: &str, data: D) -> Option<Vec<u8>> {
+        let data = Arc::from(data.into());
+        self.write()
+            .insert(key(name), data)
+            .map(|data| data.to_vec())
+    }
+
+    /// Returns the contents of a file.
+    pub fn get(&self, name: &str) -> Option<Vec<u8>> {
+        self.read().get(&key(name)).map(|data| data.to_vec())
+    }
+
+    /// Removes a file, returning its contents.
+    pub fn remove(&self, name: &str) -> Option<Vec<u8>> {
+        self.write().remove(&key(name)).map(|data| data.to_vec())
+    }
+
+    /// Whether a file exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.read().contains_key(&key(name))
+    }
+
+    /// The names of all files, in order.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.read().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // A panic cannot leave the map half updated, so a poisoned lock is
+    // still safe to use.
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Arc<[u8]>>> {
+        self.files.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Arc<[u8]>>> {
+        self.files.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A file being uploaded to a `Memory`, which holds its contents until the
+/// upload is committed.
+#[derive(Debug)]
+pub struct MemoryUpload {
+    name: String,
+    data: Vec<u8>,
+    overwrite: OverwritePolicy,
+}
+
+impl Write for MemoryUpload {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Storage for Memory {
+    type Reader = Cursor<Arc<[u8]>>;
+    type Writer = MemoryUpload;
+
+    fn open(&self, name: &str) -> Result<(Self::Reader, Option<u64>)> {
+        let name = normalize(name)?;
+        let data = match self.read().get(&key_of(&name)) {
+            Some(data) => Arc::clone(data),
+            None => return Err(ErrorKind::NotFound.into()),
+        };
+        let size = data.len() as u64;
+        Ok((Cursor::new(data), Some(size)))
+    }
+
+    fn create(&self, name: &str, overwrite: OverwritePolicy) -> Result<MemoryUpload> {
+        let name = key_of(&normalize(name)?);
+        if overwrite == OverwritePolicy::Reject && self.read().contains_key(&name) {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
+        Ok(MemoryUpload {
+            name,
+            data: Vec::new(),
+            overwrite,
+        })
+    }
+
+    fn commit(&self, upload: MemoryUpload) -> Result<()> {
+        let mut files = self.write();
+        let name = match upload.overwrite {
+            OverwritePolicy::Overwrite => upload.name,
+            OverwritePolicy::Reject if files.contains_key(&upload.name) => {
+                return Err(ErrorKind::AlreadyExists.into())
+            }
+            OverwritePolicy::Reject => upload.name,
+            OverwritePolicy::KeepBoth => {
+                let versions =
+                    (1..=MAX_VERSIONS).map(|version| format!("{}.{}", upload.name, version));
+                match std::iter::once(upload.name.clone())
+                    .chain(versions)
+                    .find(|name| !files.contains_key(name))
+                {
+                    Some(name) => name,
+                    None => return Err(ErrorKind::AlreadyExists.into()),
+                }
+            }
+        };
+        files.insert(name, Arc::from(upload.data));
+        Ok(())
+    }
+}
+
+/// The key a file is stored under. Names that no client could request are
+/// kept as they are.
+fn key(name: &str) -> String {
+    normalize(name).map_or_else(|_| name.to_string(), |name| key_of(&name))
+}
+
+fn key_of(name: &Path) -> String {
+    let parts: Vec<_> = name
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn read(storage: &Memory, name: &str) -> Result<Vec<u8>> {
+        let (mut reader, size) = storage.open(name)?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        assert_eq!(size, Some(data.len() as u64));
+        Ok(data)
+    }
+
+    fn upload(storage: &Memory, name: &str, overwrite: OverwritePolicy, data: &[u8]) -> Result<()> {
+        let mut upload = storage.create(name, overwrite)?;
+        upload.write_all(data)?;
+        storage.commit(upload)
+    }
+
+    #[test]
+    fn test_insert_and_open() {
+        let storage = Memory::new();
+        assert_eq!(storage.insert("/menus/boot.cfg", "menu"), None);
+        assert_eq!(
+            storage.insert("menus//boot.cfg", "new"),
+            Some(b"menu".to_vec())
+        );
+
+        assert_eq!(read(&storage, "menus/boot.cfg").unwrap(), b"new");
+        assert_eq!(read(&storage, "./menus/../menus/boot.cfg").unwrap(), b"new");
+        assert_eq!(storage.names(), vec!["menus/boot.cfg"]);
+
+        let error = read(&storage, "missing").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let error = read(&storage, "../boot.cfg").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+        assert_eq!(storage.remove("/menus/boot.cfg"), Some(b"new".to_vec()));
+        assert!(!storage.contains("menus/boot.cfg"));
+    }
+
+    #[test]
+    fn test_uploads_are_shared_once_committed() {
+        let storage = Memory::new();
+        let shared = storage.clone();
+
+        let mut pending = storage.create("a.cfg", OverwritePolicy::Reject).unwrap();
+        pending.write_all(b"data").unwrap();
+        assert!(!shared.contains("a.cfg"));
+
+        storage.commit(pending).unwrap();
+        assert_eq!(shared.get("a.cfg"), Some(b"data".to_vec()));
+    }
+
+    #[test]
+    fn test_overwrite_policies() {
+        let storage = Memory::new();
+        storage.insert("a.cfg", "old");
+
+        let error = upload(&storage, "a.cfg", OverwritePolicy::Reject, b"new").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+
+        upload(&storage, "a.cfg", OverwritePolicy::KeepBoth, b"one").unwrap();
+        upload(&storage, "a.cfg", OverwritePolicy::KeepBoth, b"two").unwrap();
+        assert_eq!(storage.get("a.cfg.1"), Some(b"one".to_vec()));
+        assert_eq!(storage.get("a.cfg.2"), Some(b"two".to_vec()));
+
+        upload(&storage, "a.cfg", OverwritePolicy::Overwrite, b"new").unwrap();
+        assert_eq!(storage.get("a.cfg"), Some(b"new".to_vec()));
+
+        let pending = storage.create("b.cfg", OverwritePolicy::Reject).unwrap();
+        storage.insert("b.cfg", "meanwhile");
+        let error = storage.commit(pending).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(storage.get("b.cfg"), Some(b"meanwhile".to_vec()));
+    }
+}
@@ -0,0 +1,60 @@
+//! Where a server keeps the files that clients download and upload.
+//!
+//! A `Server` hands every request to a `Storage`. By default that is a
+//! `FileSystem` rooted at the serve directory. A `Memory` keeps files in
+//! memory instead, and anything else that can look up files by name will do
+//! as well.
+
+use std::io::{Read, Result, Write};
+
+use crate::OverwritePolicy;
+
+mod casefold;
+mod fs;
+mod memory;
+mod resolve;
+mod upload;
+
+pub use fs::FileSystem;
+pub use memory::{Memory, MemoryUpload};
+pub(crate) use resolve::normalize;
+pub use resolve::SymlinkPolicy;
+pub use upload::FileUpload;
+
+/// A store of files that a server reads downloads from and writes uploads
+/// to.
+///
+/// Names are passed on as clients requested them. Errors are reported to
+/// the client with the `Code` that matches their `ErrorKind`, along with
+/// their message unless they come straight from the operating system.
+pub trait Storage: Send + Sync {
+    /// Reads a file being downloaded.
+    type Reader: Read + Send;
+
+    /// Writes a file being uploaded.
+    type Writer: Write + Send;
+
+    /// Opens the file `name` for a client to download, returning its size
+    /// as well if it is known.
+    fn open(&self, name: &str) -> Result<(Self::Reader, Option<u64>)>;
+
+    /// Creates the file `name` for a client to upload. `overwrite` says what
+    /// to do if the file already exists.
+    fn create(&self, name: &str, overwrite: OverwritePolicy) -> Result<Self::Writer>;
+
+    /// Stores an upload once its last block has been written.
+    ///
+    /// Uploads that fail are dropped without being committed, and should
+    /// leave no trace.
+    fn commit(&self, writer: Self::Writer) -> Result<()>;
+
+    /// Returns the name of the file that `name` refers to, spelled the way
+    /// this storage would look it up. The server checks uploads against its
+    /// policy by this name, so storages that read names differently from
+    /// plain `/`-separated paths must return them in that form.
+    ///
+    /// Returns `name` as it is by default.
+    fn canonical_name(&self, name: &str) -> Result<String> {
+        Ok(name.to_string())
+    }
+}
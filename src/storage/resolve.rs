@@ -0,0 +1,266 @@
+//! Maps the filenames that clients request onto paths inside the serve
+//! directory, so that no request can reach a file outside of it.
+
+use std::fs;
+use std::io::{self, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use super::casefold::CaseCache;
+
+/// How a server treats symbolic links inside its serve directory.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SymlinkPolicy {
+    /// Refuses any request whose path passes through a symbolic link.
+    Deny,
+
+    /// Follows symbolic links as long as they lead to a file inside the
+    /// serve directory.
+    #[default]
+    WithinRoot,
+
+    /// Follows symbolic links wherever they lead.
+    Follow,
+}
+
+/// Resolves requested filenames relative to a root directory.
+#[derive(Clone, Debug)]
+pub(crate) struct Resolver {
+    root: PathBuf,
+    symlinks: SymlinkPolicy,
+    windows_paths: Option<Arc<CaseCache>>,
+}
+
+impl Resolver {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_owned(),
+            symlinks: SymlinkPolicy::default(),
+            windows_paths: None,
+        }
+    }
+
+    pub fn set_symlinks(&mut self, policy: SymlinkPolicy) {
+        self.symlinks = policy;
+    }
+
+    pub fn set_windows_paths(&mut self, enabled: bool) {
+        self.windows_paths = if enabled { Some(Arc::default()) } else { None };
+    }
+
+    /// Returns the path of the file `name` refers to.
+    ///
+    /// A leading `/` is taken to mean the root. Names that climb out of the
+    /// root with `..`, or that lead out of it through a symbolic link the
+    /// policy does not allow, fail with `ErrorKind::PermissionDenied`.
+    ///
+    /// With Windows paths enabled, backslashes separate components as well,
+    /// and each component names whichever entry matches it regardless of
+    /// case.
+    pub fn resolve(&self, name: &str) -> Result<PathBuf> {
+        let relative = self.relative(name)?;
+        let path = self.root.join(&relative);
+
+        match self.symlinks {
+            SymlinkPolicy::Deny => self.deny_symlinks(&relative)?,
+            SymlinkPolicy::WithinRoot => self.contain_symlinks(&path)?,
+            SymlinkPolicy::Follow => {}
+        }
+
+        Ok(path)
+    }
+
+    /// Returns the `/`-separated path relative to the root that `resolve`
+    /// looks `name` up at.
+    pub fn canonical_name(&self, name: &str) -> Result<String> {
+        let relative = self.relative(name)?;
+        let parts = relative
+            .components()
+            .map(|component| component.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| denied("path is not valid UTF-8"))?;
+        Ok(parts.join("/"))
+    }
+
+    fn relative(&self, name: &str) -> Result<PathBuf> {
+        match &self.windows_paths {
+            Some(cache) => cache.resolve(&self.root, &normalize(&name.replace('\\', "/"))?),
+            None => normalize(name),
+        }
+    }
+
+    fn deny_symlinks(&self, relative: &Path) -> Result<()> {
+        let mut path = self.root.clone();
+        for component in relative.components() {
+            path.push(component);
+            match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    return Err(denied("symbolic links are not allowed"));
+                }
+                Ok(_) => {}
+                // Nothing below a missing component can be a link.
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn contain_symlinks(&self, path: &Path) -> Result<()> {
+        let root = self.root.canonicalize()?;
+
+        // Files that are about to be written do not exist yet, so resolve
+        // the deepest ancestor that does. The rest of the path holds no
+        // `..` components and cannot leave that ancestor.
+        let mut existing = path;
+        let resolved = loop {
+            match existing.canonicalize() {
+                Ok(resolved) => break resolved,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            existing = match existing.parent() {
+                Some(parent) => parent,
+                None => return Ok(()),
+            };
+        };
+
+        if resolved.starts_with(&root) {
+            Ok(())
+        } else {
+            Err(denied("path leads outside of the serve directory"))
+        }
+    }
+}
+
+/// Turns a requested name into a path relative to the root, resolving `.`
+/// and `..` without touching the filesystem.
+pub(crate) fn normalize(name: &str) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(denied("path leads outside of the serve directory"));
+                }
+            }
+            Component::Prefix(_) => return Err(denied("path names a drive or share")),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        return Err(denied("path does not name a file"));
+    }
+    Ok(normalized)
+}
+
+fn denied(reason: &str) -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("file").unwrap(), Path::new("file"));
+        assert_eq!(normalize("/dir/file").unwrap(), Path::new("dir/file"));
+        assert_eq!(normalize("./dir//file").unwrap(), Path::new("dir/file"));
+        assert_eq!(normalize("dir/../file").unwrap(), Path::new("file"));
+    }
+
+    #[test]
+    fn test_normalize_rejects_escapes() {
+        for name in &[
+            "..",
+            "../file",
+            "dir/../../file",
+            "/../etc/passwd",
+            "",
+            "/",
+            ".",
+        ] {
+            let error = normalize(name).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::PermissionDenied, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_resolve_stays_in_root() {
+        let root = tempfile::tempdir().unwrap();
+        let resolver = Resolver::new(root.path());
+
+        assert_eq!(
+            resolver.resolve("/etc/passwd").unwrap(),
+            root.path().join("etc/passwd")
+        );
+        assert!(resolver.resolve("../etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policies() {
+        use std::os::unix::fs::symlink;
+
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret"), b"secret").unwrap();
+
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("file"), b"file").unwrap();
+        symlink(root.path().join("file"), root.path().join("inside")).unwrap();
+        symlink(outside.path(), root.path().join("outside")).unwrap();
+
+        let mut resolver = Resolver::new(root.path());
+        assert!(resolver.resolve("inside").is_ok());
+        assert!(resolver.resolve("outside/secret").is_err());
+        assert!(resolver.resolve("outside/new").is_err());
+
+        resolver.set_symlinks(SymlinkPolicy::Deny);
+        assert!(resolver.resolve("file").is_ok());
+        assert!(resolver.resolve("missing/file").is_ok());
+        assert!(resolver.resolve("inside").is_err());
+        assert!(resolver.resolve("outside/secret").is_err());
+
+        resolver.set_symlinks(SymlinkPolicy::Follow);
+        assert_eq!(
+            resolver.resolve("outside/secret").unwrap(),
+            root.path().join("outside/secret")
+        );
+    }
+
+    #[test]
+    fn test_windows_paths() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("Boot/x64")).unwrap();
+        fs::write(root.path().join("Boot/x64/wdsnbp.com"), b"").unwrap();
+
+        let mut resolver = Resolver::new(root.path());
+        resolver.set_windows_paths(true);
+        assert_eq!(
+            resolver.resolve(r"\boot\X64\WDSNBP.com").unwrap(),
+            root.path().join("Boot/x64/wdsnbp.com")
+        );
+        assert_eq!(
+            resolver.resolve(r"boot\new.log").unwrap(),
+            root.path().join("Boot/new.log")
+        );
+        let error = resolver.resolve(r"boot\..\..\secret").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(
+            resolver
+                .canonical_name(r"BOOT\x64\..\X64\wdsnbp.COM")
+                .unwrap(),
+            "Boot/x64/wdsnbp.com"
+        );
+        assert_eq!(resolver.canonical_name(r"boot\..\new").unwrap(), "new");
+
+        resolver.set_windows_paths(false);
+        assert_eq!(
+            resolver.resolve("boot/x64/wdsnbp.com").unwrap(),
+            root.path().join("boot/x64/wdsnbp.com")
+        );
+    }
+}
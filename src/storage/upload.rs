@@ -0,0 +1,226 @@
+//! Uploads are written to a hidden temporary file and only moved into place
+//! once they are complete, so that no client ever sees a partial file.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::OverwritePolicy;
+
+/// How many suffixed names `OverwritePolicy::KeepBoth` tries.
+const MAX_VERSIONS: u32 = 10_000;
+
+/// A file being uploaded to a `FileSystem`.
+///
+/// The upload is written to a hidden temporary file next to where it is to
+/// end up, which is removed unless the upload is committed.
+#[derive(Debug)]
+pub struct FileUpload {
+    file: File,
+    temp: PathBuf,
+    path: PathBuf,
+    overwrite: OverwritePolicy,
+    committed: bool,
+}
+
+impl FileUpload {
+    /// Starts an upload that is to end up at `path`.
+    ///
+    /// Fails with `ErrorKind::AlreadyExists` right away if the file exists
+    /// and `overwrite` does not allow replacing it.
+    pub(crate) fn new(path: PathBuf, overwrite: OverwritePolicy) -> Result<Self> {
+        if overwrite == OverwritePolicy::Reject && fs::symlink_metadata(&path).is_ok() {
+            return Err(ErrorKind::AlreadyExists.into());
+        }
+
+        let (dir, name) = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) => (dir, name),
+            _ => return Err(ErrorKind::InvalidInput.into()),
+        };
+
+        let mut rng = rand::thread_rng();
+        loop {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(name);
+            temp_name.push(format!(".{:08x}.part", rng.gen::<u32>()));
+            let temp = dir.join(temp_name);
+
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => {
+                    return Ok(Self {
+                        file,
+                        temp,
+                        path,
+                        overwrite,
+                        committed: false,
+                    })
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Flushes the upload to disk and moves it into place, returning where
+    /// it ended up.
+    pub(crate) fn commit(mut self) -> Result<PathBuf> {
+        self.file.sync_all()?;
+
+        let path = match self.overwrite {
+            OverwritePolicy::Overwrite => {
+                fs::rename(&self.temp, &self.path)?;
+                self.path.clone()
+            }
+            OverwritePolicy::Reject => self.link(self.path.clone())?,
+            OverwritePolicy::KeepBoth => {
+                let mut versions = (1..=MAX_VERSIONS).map(|version| versioned(&self.path, version));
+                let mut candidate = self.path.clone();
+                loop {
+                    match self.link(candidate) {
+                        Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                        result => break result?,
+                    }
+                    candidate = match versions.next() {
+                        Some(candidate) => candidate,
+                        None => return Err(ErrorKind::AlreadyExists.into()),
+                    };
+                }
+            }
+        };
+        self.committed = true;
+
+        sync_dir(&path)?;
+        Ok(path)
+    }
+
+    /// Gives the upload the name `path` unless a file by that name exists,
+    /// which a rename would replace.
+    fn link(&self, path: PathBuf) -> Result<PathBuf> {
+        fs::hard_link(&self.temp, &path)?;
+        let _ = fs::remove_file(&self.temp);
+        Ok(path)
+    }
+}
+
+impl Write for FileUpload {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for FileUpload {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+fn versioned(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", version));
+    PathBuf::from(name)
+}
+
+/// Makes the new name of a file durable.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> Result<()> {
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn upload(path: &Path, overwrite: OverwritePolicy, data: &[u8]) -> Result<PathBuf> {
+        let mut upload = FileUpload::new(path.to_owned(), overwrite)?;
+        upload.write_all(data)?;
+        upload.commit()
+    }
+
+    #[test]
+    fn test_upload_is_hidden_until_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.cfg");
+
+        let mut upload = FileUpload::new(path.clone(), OverwritePolicy::Reject).unwrap();
+        upload.write_all(b"data").unwrap();
+        assert!(!path.exists());
+        assert!(entries(dir.path())[0].starts_with(".a.cfg."));
+
+        assert_eq!(upload.commit().unwrap(), path);
+        assert_eq!(entries(dir.path()), vec!["a.cfg"]);
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+    }
+
+    #[test]
+    fn test_abandoned_upload_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut upload =
+            FileUpload::new(dir.path().join("a.cfg"), OverwritePolicy::Reject).unwrap();
+        upload.write_all(b"partial").unwrap();
+        drop(upload);
+
+        assert!(entries(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_overwrite_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.cfg");
+        fs::write(&path, b"old").unwrap();
+
+        let error = upload(&path, OverwritePolicy::Reject, b"new").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+
+        let kept = upload(&path, OverwritePolicy::KeepBoth, b"one").unwrap();
+        assert_eq!(kept, dir.path().join("a.cfg.1"));
+        upload(&path, OverwritePolicy::KeepBoth, b"two").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(fs::read(dir.path().join("a.cfg.1")).unwrap(), b"one");
+        assert_eq!(fs::read(dir.path().join("a.cfg.2")).unwrap(), b"two");
+
+        upload(&path, OverwritePolicy::Overwrite, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(entries(dir.path()), vec!["a.cfg", "a.cfg.1", "a.cfg.2"]);
+    }
+
+    #[test]
+    fn test_commit_does_not_replace_a_file_created_meanwhile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.cfg");
+
+        let upload = FileUpload::new(path.clone(), OverwritePolicy::Reject).unwrap();
+        fs::write(&path, b"other").unwrap();
+
+        let error = upload.commit().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"other");
+        assert_eq!(entries(dir.path()), vec!["a.cfg"]);
+    }
+}
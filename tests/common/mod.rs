@@ -0,0 +1,46 @@
+//! A server and clients for the integration tests to talk to each other
+//! with.
+
+// Not every test binary uses every helper.
+#![allow(dead_code)]
+
+use std::io;
+use std::thread::{self, JoinHandle};
+
+use tftp::client::{self, ConnectTo};
+use tftp::{Client, Server, Storage};
+
+/// Serves `requests` requests on a thread of its own, handling each of them
+/// on a thread of their own too, so that a handler waiting out a lost ACK
+/// does not hold up the next request. The thread returns what handling each
+/// request came to, in the order the requests arrived.
+pub fn serve<S: Storage + 'static>(
+    server: Server<S>,
+    requests: usize,
+) -> JoinHandle<Vec<io::Result<()>>> {
+    thread::spawn(move || {
+        let handlers: Vec<_> = (0..requests)
+            .map(|_| {
+                let handler = server.serve().unwrap();
+                thread::spawn(move || handler.handle())
+            })
+            .collect();
+        handlers
+            .into_iter()
+            .map(|handler| handler.join().unwrap())
+            .collect()
+    })
+}
+
+/// Returns a client builder for the server on `port`, to set options on.
+pub fn builder(port: u16) -> client::Builder<ConnectTo> {
+    client::Builder::new()
+        .unwrap()
+        .connect_to(("127.0.0.1", port))
+        .unwrap()
+}
+
+/// Returns a client for the server on `port`.
+pub fn client(port: u16) -> Client {
+    builder(port).build()
+}
@@ -40,11 +40,11 @@ struct ErroneousWriter;
 
 impl io::Write for ErroneousWriter {
     fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
-        Err(io::Error::new(io::ErrorKind::Other, format!("Fake error")))
+        Err(io::Error::other("Fake error"))
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Err(io::Error::new(io::ErrorKind::Other, format!("Fake error")))
+        Err(io::Error::other("Fake error"))
     }
 }
 
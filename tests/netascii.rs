@@ -0,0 +1,64 @@
+use std::fs;
+use std::net::UdpSocket;
+use std::thread;
+
+use tftp::packet::Mode;
+use tftp::Server;
+
+mod common;
+
+use common::{client, serve};
+
+#[test]
+fn test_get_translates_netascii() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket
+            .send(b"\0\x03\0\x01line\r\nbare\r\x00cr\r\n")
+            .unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 1]);
+    });
+
+    let actual = client(port)
+        .get("config", Mode::NetAscii, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"line\nbare\rcr\n");
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_server_translates_netascii() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    fs::write(serve_dir.path().join("motd"), b"hello\nworld\r").unwrap();
+
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server_thread = serve(server, 2);
+
+    // Read the file as the raw bytes sent on the wire.
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"\0\x01motd\0netascii\0", ("127.0.0.1", port))
+        .unwrap();
+    let mut buf = [0; 1024];
+    let (nbytes, handler) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..nbytes], b"\0\x03\0\x01hello\r\nworld\r\x00");
+    socket.send_to(&[0, 4, 0, 1], handler).unwrap();
+
+    client(port)
+        .put("uploaded", Mode::NetAscii, &b"one\ntwo\r\n"[..])
+        .unwrap();
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+
+    let uploaded = fs::read(serve_dir.path().join("uploaded")).unwrap();
+    assert_eq!(&uploaded[..], b"one\ntwo\r\n");
+}
@@ -0,0 +1,267 @@
+use std::net::UdpSocket;
+use std::thread;
+
+use tftp::packet::Mode;
+use tftp::Server;
+
+mod common;
+
+use common::{builder, client, serve};
+
+#[test]
+fn test_get_with_unsupported_option() {
+    let exemplar = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/artifacts/alice-in-wonderland.txt"
+    ));
+
+    let serve_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/artifacts");
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir).unwrap();
+
+    let server_thread = serve(server, 1);
+
+    let client = builder(port).option("x-unsupported", "1").build();
+
+    let actual = client
+        .get("alice-in-wonderland.txt", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], &exemplar[..]);
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_get_acknowledges_oack() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (nbytes, client) = listener.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], b"\0\x01file\0octet\0x-option\x001\0");
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(b"\0\x06x-option\x001\0").unwrap();
+
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 0]);
+
+        socket.send(b"\0\x03\0\x01hello").unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 1]);
+    });
+
+    let client = builder(port).option("x-option", "1").build();
+
+    let actual = client.get("file", Mode::Octet, Vec::new()).unwrap();
+    assert_eq!(&actual[..], b"hello");
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_oack_with_unrequested_option_is_rejected() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(b"\0\x06x-surprise\x001\0").unwrap();
+
+        // The client must terminate the transfer with error code 8.
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..4], &[0, 5, 0, 8]);
+        assert_eq!(buf[nbytes - 1], 0);
+    });
+
+    client(port)
+        .put("file", Mode::Octet, &b"hello"[..])
+        .unwrap_err();
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_get_with_block_size() {
+    let exemplar = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/artifacts/alice-in-wonderland.txt"
+    ));
+
+    let serve_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/artifacts");
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir).unwrap();
+
+    let server_thread = serve(server, 1);
+
+    let client = builder(port).block_size(1428).build();
+
+    let actual = client
+        .get("alice-in-wonderland.txt", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], &exemplar[..]);
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_put_with_block_size() {
+    let data = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/artifacts/alice-in-wonderland.txt"
+    ));
+
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+
+    let server_thread = serve(server, 1);
+
+    let client = builder(port).block_size(8192).build();
+
+    client
+        .put("alice-in-wonderland.txt", Mode::Octet, &data[..])
+        .unwrap();
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+
+    let actual = std::fs::read(serve_dir.path().join("alice-in-wonderland.txt")).unwrap();
+    assert_eq!(&actual[..], &data[..]);
+}
+
+#[test]
+fn test_server_offers_smaller_block_size() {
+    let serve_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/artifacts");
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir).unwrap();
+    let server = server.max_block_size(1024);
+
+    let server_thread = serve(server, 1);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(
+            b"\0\x01alice-in-wonderland.txt\0octet\0blksize\x001428\0",
+            ("127.0.0.1", port),
+        )
+        .unwrap();
+
+    let mut buf = [0; 2048];
+    let (nbytes, handler) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..nbytes], b"\0\x06blksize\x001024\0");
+
+    socket.send_to(&[0, 4, 0, 0], handler).unwrap();
+    let (nbytes, _) = socket.recv_from(&mut buf).unwrap();
+    assert_eq!(&buf[..4], &[0, 3, 0, 1]);
+    assert_eq!(nbytes, 4 + 1024);
+
+    // Abandon the transfer.
+    socket.send_to(b"\0\x05\0\0\0", handler).unwrap();
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_get_reports_transfer_size() {
+    let exemplar = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/artifacts/alice-in-wonderland.txt"
+    ));
+
+    let serve_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/artifacts");
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir).unwrap();
+
+    let server_thread = serve(server, 1);
+
+    let client = builder(port).transfer_size(0).timeout(5).build();
+
+    let mut reported = None;
+    let actual = client
+        .get_with("alice-in-wonderland.txt", Mode::Octet, |size| {
+            reported = size;
+            Vec::with_capacity(size.unwrap_or(0) as usize)
+        })
+        .unwrap();
+    assert_eq!(reported, Some(exemplar.len() as u64));
+    assert_eq!(&actual[..], &exemplar[..]);
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_put_rejected_by_transfer_size() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server.max_upload_size(1024);
+
+    let server_thread = serve(server, 1);
+
+    let data = [0u8; 2048];
+    let client = builder(port).transfer_size(data.len() as u64).build();
+
+    let error = client
+        .put("too-big.bin", Mode::Octet, &data[..])
+        .unwrap_err();
+    assert!(format!("{}", error).contains("exceeds"));
+
+    assert!(server_thread.join().unwrap()[0].is_err());
+    assert!(!serve_dir.path().join("too-big.bin").exists());
+}
+
+#[test]
+fn test_get_and_put_with_window_size() {
+    let data = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/artifacts/alice-in-wonderland.txt"
+    ));
+
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+
+    let server_thread = serve(server, 2);
+
+    let builder = builder(port).block_size(1024).window_size(8);
+
+    builder
+        .try_clone()
+        .unwrap()
+        .build()
+        .put("alice-in-wonderland.txt", Mode::Octet, &data[..])
+        .unwrap();
+
+    let actual = builder
+        .build()
+        .get("alice-in-wonderland.txt", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], &data[..]);
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_put_requires_ack_for_block_zero() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(&[0, 4, 0, 5]).unwrap();
+
+        // The client must not start sending data, but terminate the
+        // transfer with error code 4.
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..4], &[0, 5, 0, 4]);
+        assert_eq!(buf[nbytes - 1], 0);
+    });
+
+    client(port)
+        .put("file", Mode::Octet, &b"hello"[..])
+        .unwrap_err();
+
+    server_thread.join().unwrap();
+}
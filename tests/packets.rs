@@ -0,0 +1,144 @@
+//! Property tests for decoding and encoding packets.
+//!
+//! Servers decode whatever arrives on their socket, so decoding must never
+//! panic, no matter how the datagram is crafted.
+
+use std::io::ErrorKind;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use proptest::prelude::*;
+
+use tftp::packet::*;
+
+mod common;
+
+use common::builder;
+
+fn string() -> impl Strategy<Value = String> {
+    "[^\x00]{0,16}"
+}
+
+fn mode() -> impl Strategy<Value = Mode> {
+    prop_oneof![Just(Mode::NetAscii), Just(Mode::Octet), Just(Mode::Mail)]
+}
+
+fn options() -> impl Strategy<Value = Options> {
+    prop::collection::vec((string(), string()), 0..4).prop_map(|pairs| pairs.into_iter().collect())
+}
+
+fn code() -> impl Strategy<Value = Code> {
+    (0u16..=8).prop_map(|code| Code::from_u16(code).unwrap())
+}
+
+fn block() -> impl Strategy<Value = Block> {
+    any::<u16>().prop_map(Block::new)
+}
+
+fn any_packet() -> impl Strategy<Value = AnyPacket> {
+    prop_oneof![
+        (string(), mode(), options())
+            .prop_map(|(file, mode, options)| Packet::rrq_with_options(file, mode, options).into()),
+        (string(), mode(), options())
+            .prop_map(|(file, mode, options)| Packet::wrq_with_options(file, mode, options).into()),
+        (block(), prop::collection::vec(any::<u8>(), 0..600))
+            .prop_map(|(block, data)| Packet::data(block, data).into()),
+        block().prop_map(|block| Packet::ack(block).into()),
+        (code(), string()).prop_map(|(code, message)| Packet::error(code, message).into()),
+        options()
+            .prop_filter("an OACK acknowledges at least one option", |options| {
+                !options.is_empty()
+            })
+            .prop_map(|options| Packet::oack(options).into()),
+    ]
+}
+
+/// Arbitrary bytes behind a valid opcode, so that the body parsers are
+/// exercised rather than just the opcode check.
+fn datagram() -> impl Strategy<Value = Vec<u8>> {
+    (0u16..=7, prop::collection::vec(any::<u8>(), 0..64)).prop_map(|(opcode, body)| {
+        let mut bytes = opcode.to_be_bytes().to_vec();
+        bytes.extend(body);
+        bytes
+    })
+}
+
+proptest! {
+    #[test]
+    fn decoding_arbitrary_bytes_does_not_panic(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+        let _ = AnyPacket::decode(&bytes[..]);
+    }
+
+    #[test]
+    fn decoding_arbitrary_bodies_does_not_panic(bytes in datagram()) {
+        let _ = AnyPacket::decode(&bytes[..]);
+        let _ = Packet::<Rrq>::decode(&bytes[..]);
+        let _ = Packet::<Wrq>::decode(&bytes[..]);
+        let _ = Packet::<Data>::decode(&bytes[..]);
+        let _ = Packet::<Ack>::decode(&bytes[..]);
+        let _ = Packet::<Error>::decode(&bytes[..]);
+        let _ = Packet::<Oack>::decode(&bytes[..]);
+    }
+
+    #[test]
+    fn decoded_packets_round_trip(bytes in datagram()) {
+        if let Ok(packet) = AnyPacket::decode(&bytes[..]) {
+            let encoded = packet.encode().unwrap();
+            prop_assert_eq!(AnyPacket::decode(&encoded[..]), Ok(packet));
+        }
+    }
+
+    #[test]
+    fn encoded_packets_round_trip(packet in any_packet()) {
+        let encoded = packet.encode().unwrap();
+        prop_assert_eq!(AnyPacket::decode(&encoded[..]), Ok(packet.clone()));
+
+        let mut buf = vec![0; encoded.len()];
+        prop_assert_eq!(packet.encode_into(&mut buf), Ok(encoded.len()));
+        prop_assert_eq!(&buf, &encoded);
+
+        if !encoded.is_empty() {
+            let mut buf = vec![0; encoded.len() - 1];
+            prop_assert_eq!(
+                packet.encode_into(&mut buf),
+                Err(EncodeError::BufferTooSmall { needed: encoded.len() })
+            );
+        }
+    }
+
+    #[test]
+    fn typed_packets_round_trip(block in block(), data in prop::collection::vec(any::<u8>(), 0..600)) {
+        let packet = Packet::data(block, data);
+        let encoded = packet.encode().unwrap();
+        prop_assert_eq!(Packet::<Data>::decode(&encoded[..]), Ok(packet));
+
+        let packet = Packet::ack(block);
+        let encoded = packet.encode().unwrap();
+        prop_assert_eq!(Packet::<Ack>::decode(&encoded[..]), Ok(packet));
+    }
+}
+
+#[test]
+fn test_requests_that_cannot_be_encoded_are_not_sent() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let error = builder(port)
+        .build()
+        .get("boot\0.cfg", Mode::Octet, Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+    let error = builder(port)
+        .option("x-option", "1\0")
+        .build()
+        .put("boot.cfg", Mode::Octet, &b"data"[..])
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+
+    listener
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .unwrap();
+    let mut buf = [0; 1024];
+    assert!(listener.recv_from(&mut buf).is_err());
+}
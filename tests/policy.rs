@@ -0,0 +1,133 @@
+use std::io::ErrorKind;
+
+use tftp::packet::Mode;
+use tftp::{OverwritePolicy, Server};
+
+mod common;
+
+use common::{client, serve};
+
+#[test]
+fn test_read_only_server_refuses_uploads() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::write(serve_dir.path().join("boot.img"), b"kernel").unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server_thread = serve(server.allow_writes(false), 2);
+
+    let actual = client(port)
+        .get("boot.img", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"kernel");
+
+    let error = client(port)
+        .put("upload", Mode::Octet, &b"data"[..])
+        .unwrap_err();
+    assert!(error.to_string().contains("uploads are disabled"));
+    assert!(!serve_dir.path().join("upload").exists());
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_write_only_server_refuses_downloads() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::write(serve_dir.path().join("router.cfg"), b"config").unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server_thread = serve(server.allow_reads(false), 2);
+
+    let error = client(port)
+        .get("router.cfg", Mode::Octet, Vec::new())
+        .unwrap_err();
+    assert!(error.to_string().contains("downloads are disabled"));
+
+    client(port)
+        .put("switch.cfg", Mode::Octet, &b"data"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+
+    let actual = std::fs::read(serve_dir.path().join("switch.cfg")).unwrap();
+    assert_eq!(&actual[..], b"data");
+}
+
+#[test]
+fn test_uploads_are_confined_to_upload_dirs() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(serve_dir.path().join("backups")).unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server_thread = serve(server.upload_dir("backups"), 2);
+
+    let error = client(port)
+        .put("router.cfg", Mode::Octet, &b"data"[..])
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    assert!(!serve_dir.path().join("router.cfg").exists());
+
+    client(port)
+        .put("backups/router.cfg", Mode::Octet, &b"data"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+
+    let actual = std::fs::read(serve_dir.path().join("backups/router.cfg")).unwrap();
+    assert_eq!(&actual[..], b"data");
+}
+
+#[test]
+fn test_windows_paths_stay_in_upload_dirs() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(serve_dir.path().join("backups")).unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server.windows_paths(true).upload_dir("backups");
+    let server_thread = serve(server, 3);
+
+    for name in &[r"backups/..\escaped.cfg", r"BACKUPS\..\escaped.cfg"] {
+        let error = client(port)
+            .put(*name, Mode::Octet, &b"data"[..])
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied, "{}", name);
+    }
+    assert!(!serve_dir.path().join("escaped.cfg").exists());
+
+    client(port)
+        .put(r"Backups\router.cfg", Mode::Octet, &b"data"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+
+    let actual = std::fs::read(serve_dir.path().join("backups/router.cfg")).unwrap();
+    assert_eq!(&actual[..], b"data");
+}
+
+#[test]
+fn test_overwrite_policies() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(serve_dir.path().join("backups")).unwrap();
+    std::fs::create_dir(serve_dir.path().join("latest")).unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server
+        .overwrite_in("backups", OverwritePolicy::KeepBoth)
+        .overwrite_in("latest", OverwritePolicy::Overwrite);
+    let server_thread = serve(server, 6);
+
+    for data in &[&b"monday"[..], b"tuesday"] {
+        client(port)
+            .put("backups/router.cfg", Mode::Octet, *data)
+            .unwrap();
+        client(port)
+            .put("latest/router.cfg", Mode::Octet, *data)
+            .unwrap();
+    }
+
+    client(port)
+        .put("router.cfg", Mode::Octet, &b"monday"[..])
+        .unwrap();
+    let error = client(port)
+        .put("router.cfg", Mode::Octet, &b"tuesday"[..])
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    server_thread.join().unwrap();
+
+    let read = |name: &str| std::fs::read(serve_dir.path().join(name)).unwrap();
+    assert_eq!(read("backups/router.cfg"), b"monday");
+    assert_eq!(read("backups/router.cfg.1"), b"tuesday");
+    assert_eq!(read("latest/router.cfg"), b"tuesday");
+    assert_eq!(read("router.cfg"), b"monday");
+}
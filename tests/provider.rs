@@ -0,0 +1,45 @@
+use std::io::ErrorKind;
+
+use tftp::packet::Mode;
+use tftp::{Content, ReadRequest, Server};
+
+mod common;
+
+use common::{builder, client, serve};
+
+#[test]
+fn test_provider_generates_content() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    std::fs::write(serve_dir.path().join("kernel"), b"kernel").unwrap();
+    std::fs::write(serve_dir.path().join("secret"), b"secret").unwrap();
+
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server.provider(|request: &ReadRequest<'_>| match request.filename {
+        "pxelinux.cfg/default" => {
+            let block_size = request.negotiated.get("blksize").unwrap_or("512");
+            let menu = format!("{} {}", request.client.ip(), block_size);
+            Ok(Some(Content::from(menu)))
+        }
+        "secret" => Err(ErrorKind::NotFound.into()),
+        _ => Ok(None),
+    });
+
+    let server_thread = serve(server, 3);
+
+    let actual = builder(port)
+        .block_size(1024)
+        .build()
+        .get("pxelinux.cfg/default", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"127.0.0.1 1024");
+
+    let actual = client(port).get("kernel", Mode::Octet, Vec::new()).unwrap();
+    assert_eq!(&actual[..], b"kernel");
+
+    let error = client(port)
+        .get("secret", Mode::Octet, Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+
+    server_thread.join().unwrap();
+}
@@ -45,7 +45,7 @@ struct ErroneousReader;
 
 impl io::Read for ErroneousReader {
     fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
-        Err(io::Error::new(io::ErrorKind::Other, format!("Fake error")))
+        Err(io::Error::other("Fake error"))
     }
 }
 
@@ -120,3 +120,30 @@ fn test_put_when_already_exists() {
         .put("alice-in-wonderland.txt", Mode::NetAscii, &data[..])
         .unwrap();
 }
+
+#[test]
+fn test_abandoned_put_leaves_no_file() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server
+        .retransmit_timeout(std::time::Duration::from_millis(50))
+        .max_retries(1);
+
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"\0\x02boot.cfg\0octet\0", ("127.0.0.1", port))
+        .unwrap();
+    let handler = server.serve().unwrap();
+    let server_thread = thread::spawn(move || handler.handle());
+
+    // Send the first of several blocks, then go away.
+    let mut buf = [0; 16];
+    let (_, handler_addr) = socket.recv_from(&mut buf).unwrap();
+    let mut block = vec![0, 3, 0, 1];
+    block.extend_from_slice(&[b'x'; 512]);
+    socket.send_to(&block[..], handler_addr).unwrap();
+
+    let error = server_thread.join().unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(std::fs::read_dir(serve_dir.path()).unwrap().count(), 0);
+}
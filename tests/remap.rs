@@ -0,0 +1,40 @@
+use std::io::ErrorKind;
+
+use tftp::packet::Mode;
+use tftp::storage::Memory;
+use tftp::{RemapRule, Server};
+
+mod common;
+
+use common::{client, serve};
+
+#[test]
+fn test_remap_rules_rewrite_requests() {
+    let files = Memory::new();
+    files.insert("grub/grubx64.efi", "grub");
+    files.insert("secret.key", "secret");
+
+    let server = Server::with_storage("127.0.0.1:0", files.clone())
+        .unwrap()
+        .remap(RemapRule::rewrite(r"^(?i)/?efi/boot/", "grub/").unwrap())
+        .remap(RemapRule::deny(r"\.key$").unwrap().reads_only())
+        .remap(RemapRule::rewrite(r"^", "uploads/").unwrap().writes_only());
+    let port = server.local_addr().unwrap().port();
+    let server_thread = serve(server, 3);
+
+    let actual = client(port)
+        .get("/EFI/BOOT/grubx64.efi", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"grub");
+
+    let error = client(port)
+        .get("secret.key", Mode::Octet, Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+    client(port)
+        .put("host.key", Mode::Octet, &b"key"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+    assert_eq!(files.get("uploads/host.key"), Some(b"key".to_vec()));
+}
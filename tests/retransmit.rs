@@ -0,0 +1,134 @@
+use std::io;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::Duration;
+
+use tftp::packet::Mode;
+
+mod common;
+
+use common::builder;
+
+#[test]
+fn test_client_retransmits_request() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+
+        // Ignore the first request as if it was lost.
+        let (first, _) = listener.recv_from(&mut buf).unwrap();
+        let first = buf[..first].to_vec();
+        let (second, client) = listener.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..second], &first[..]);
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(b"\0\x03\0\x01hello").unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 1]);
+    });
+
+    let client = builder(port)
+        .retransmit_timeout(Duration::from_millis(100))
+        .build();
+
+    let actual = client.get("file", Mode::Octet, Vec::new()).unwrap();
+    assert_eq!(&actual[..], b"hello");
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_client_times_out() {
+    // Nobody ever answers on this socket.
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let client = builder(port)
+        .retransmit_timeout(Duration::from_millis(20))
+        .max_retries(2)
+        .build();
+
+    let error = client.put("file", Mode::Octet, &b"hello"[..]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+    listener
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    let mut buf = [0; 1024];
+    for _ in 0..3 {
+        listener.recv_from(&mut buf).unwrap();
+    }
+    assert!(listener.recv_from(&mut buf).is_err());
+}
+
+#[test]
+fn test_get_acknowledges_repeated_oack() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(b"\0\x06x-option\x001\0").unwrap();
+
+        // Drop the ACK for block 0 as if it was lost, and send the OACK
+        // again.
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 0]);
+        socket.send(b"\0\x06x-option\x001\0").unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 0]);
+
+        socket.send(b"\0\x03\0\x01hello").unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 1]);
+    });
+
+    let client = builder(port).option("x-option", "1").build();
+
+    let actual = client.get("file", Mode::Octet, Vec::new()).unwrap();
+    assert_eq!(&actual[..], b"hello");
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_put_ignores_repeated_oack() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        socket.send(b"\0\x06x-option\x001\0").unwrap();
+
+        // Drop the first block as if it was lost, and send the OACK again.
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], b"\0\x03\0\x01hello");
+        socket.send(b"\0\x06x-option\x001\0").unwrap();
+
+        // The client carries on and sends the block again once its timeout
+        // elapses.
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], b"\0\x03\0\x01hello");
+        socket.send(&[0, 4, 0, 1]).unwrap();
+    });
+
+    let client = builder(port)
+        .option("x-option", "1")
+        .retransmit_timeout(Duration::from_millis(100))
+        .build();
+
+    client.put("file", Mode::Octet, &b"hello"[..]).unwrap();
+
+    server_thread.join().unwrap();
+}
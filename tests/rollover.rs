@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use tftp::packet::{Mode, Rollover};
+use tftp::Server;
+
+mod common;
+
+use common::{builder, serve};
+
+/// Enough 8 byte blocks for the block number to wrap around once.
+const DATA_SIZE: usize = 8 * 70_000 + 3;
+
+fn test_rollover(rollover: Option<Rollover>) {
+    let data: Vec<u8> = (0..DATA_SIZE).map(|i| (i % 251) as u8).collect();
+
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server.retransmit_timeout(Duration::from_millis(500));
+
+    let server_thread = serve(server, 2);
+
+    let mut builder = builder(port)
+        .block_size(8)
+        .window_size(64)
+        .retransmit_timeout(Duration::from_millis(500));
+    if let Some(rollover) = rollover {
+        builder = builder.rollover(rollover);
+    }
+
+    builder
+        .try_clone()
+        .unwrap()
+        .build()
+        .put("large.bin", Mode::Octet, &data[..])
+        .unwrap();
+
+    let actual = builder
+        .build()
+        .get("large.bin", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(actual.len(), data.len());
+    assert!(actual == data);
+
+    assert!(server_thread.join().unwrap().iter().all(Result::is_ok));
+}
+
+#[test]
+fn test_rollover_to_zero_by_default() {
+    test_rollover(None);
+}
+
+#[test]
+fn test_rollover_to_one() {
+    test_rollover(Some(Rollover::One));
+}
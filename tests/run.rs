@@ -0,0 +1,159 @@
+use std::net::UdpSocket;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tftp::packet::{AnyPacket, Code, Mode};
+use tftp::{BusyPolicy, Server};
+
+mod common;
+
+use common::client;
+
+fn start(server: Server) -> u16 {
+    let port = server.local_addr().unwrap().port();
+    thread::spawn(move || server.run());
+    port
+}
+
+fn get(port: u16, file: &str) -> std::io::Result<Vec<u8>> {
+    client(port).get(file, Mode::Octet, Vec::new())
+}
+
+/// Starts a download of `file` that is never acknowledged, returning the
+/// socket and the address of the transfer.
+fn stall(port: u16, file: &str) -> (UdpSocket, std::net::SocketAddr) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let request = format!("\x00\x01{}\x00octet\x00", file);
+    socket
+        .send_to(request.as_bytes(), ("127.0.0.1", port))
+        .unwrap();
+
+    let mut buf = [0; 1024];
+    let (nbytes, tid) = socket.recv_from(&mut buf).unwrap();
+    match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+        AnyPacket::Data(_) => (socket, tid),
+        other => panic!("expected a data packet but got {:?}", other),
+    }
+}
+
+/// Sends a request for `file` from a new socket, without waiting for an
+/// answer.
+fn request(port: u16, file: &str) -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let request = format!("\x00\x01{}\x00octet\x00", file);
+    socket
+        .send_to(request.as_bytes(), ("127.0.0.1", port))
+        .unwrap();
+    socket
+}
+
+fn assert_busy(socket: &UdpSocket) {
+    let mut buf = [0; 1024];
+    let nbytes = socket.recv(&mut buf).unwrap();
+    match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+        AnyPacket::Error(error) => assert_eq!(error.body.code, Code::NotDefined),
+        other => panic!("expected an error packet but got {:?}", other),
+    }
+}
+
+fn serve_dir() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("small"), b"small file").unwrap();
+    std::fs::write(dir.path().join("large"), vec![7; 2048]).unwrap();
+    dir
+}
+
+#[test]
+fn test_run_serves_transfers_at_once() {
+    let dir = serve_dir();
+    let server = Server::new("127.0.0.1:0", dir.path())
+        .unwrap()
+        .max_transfers(4);
+    let port = start(server);
+
+    let clients: Vec<_> = (0..8)
+        .map(|_| thread::spawn(move || get(port, "large")))
+        .collect();
+    for client in clients {
+        assert_eq!(client.join().unwrap().unwrap(), vec![7; 2048]);
+    }
+}
+
+#[test]
+fn test_run_rejects_when_busy() {
+    let dir = serve_dir();
+    let server = Server::new("127.0.0.1:0", dir.path())
+        .unwrap()
+        .max_transfers(1)
+        .when_busy(BusyPolicy::Reject);
+    let port = start(server);
+    let (_stalled, _) = stall(port, "large");
+
+    assert_busy(&request(port, "small"));
+}
+
+#[test]
+fn test_run_queues_when_busy() {
+    let dir = serve_dir();
+    let server = Server::new("127.0.0.1:0", dir.path())
+        .unwrap()
+        .max_transfers(1);
+    let port = start(server);
+    let (stalled, tid) = stall(port, "large");
+
+    let (done, result) = mpsc::channel();
+    thread::spawn(move || done.send(get(port, "small")).unwrap());
+    thread::sleep(Duration::from_millis(500));
+    assert!(result.try_recv().is_err());
+
+    // Aborting the first transfer frees up the worker.
+    stalled.send_to(b"\x00\x05\x00\x00abort\x00", tid).unwrap();
+    let actual = result.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(actual.unwrap(), b"small file");
+}
+
+#[test]
+fn test_run_rejects_when_queue_is_full() {
+    let dir = serve_dir();
+    let server = Server::new("127.0.0.1:0", dir.path())
+        .unwrap()
+        .max_transfers(1)
+        .max_queued(1);
+    let port = start(server);
+    let (_stalled, _) = stall(port, "large");
+
+    let _queued = request(port, "small");
+    thread::sleep(Duration::from_millis(100));
+    assert_busy(&request(port, "small"));
+}
+
+#[test]
+fn test_run_drops_requests_clients_gave_up_on() {
+    let dir = serve_dir();
+    let server = Server::new("127.0.0.1:0", dir.path())
+        .unwrap()
+        .max_transfers(1)
+        .retransmit_timeout(Duration::from_millis(200))
+        .max_retries(1);
+    let port = start(server);
+    let (slow, tid) = stall(port, "large");
+
+    // The queued request outlives the 400ms the server waits for a client,
+    // while the slow transfer keeps the worker busy for longer than that.
+    let queued = request(port, "small");
+    let mut buf = [0; 1024];
+    for block in 1..=5u8 {
+        thread::sleep(Duration::from_millis(150));
+        slow.send_to(&[0, 4, 0, block], tid).unwrap();
+        if block < 5 {
+            slow.recv(&mut buf).unwrap();
+        }
+    }
+
+    queued
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(queued.recv(&mut buf).is_err());
+    assert_eq!(get(port, "small").unwrap(), b"small file");
+}
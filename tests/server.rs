@@ -1,12 +1,13 @@
 use std::net::UdpSocket;
 
+use tftp::packet::{AnyPacket, Code};
 use tftp::Server;
 
 #[test]
 fn test_serve_when_request_is_not_read_or_write() {
     let serve_dir = tempfile::tempdir().unwrap();
     let serve_addr = "127.0.0.1";
-    let (port, server) = Server::random_port(&serve_addr, serve_dir.path()).unwrap();
+    let (port, server) = Server::random_port(serve_addr, serve_dir.path()).unwrap();
 
     let socket = UdpSocket::bind("0.0.0.0:12345").unwrap();
 
@@ -18,8 +19,114 @@ fn test_serve_when_request_is_not_read_or_write() {
     bytes.append(&mut message);
 
     let _ = socket
-        .send_to(&bytes[..], &format!("{}:{}", serve_addr, port))
+        .send_to(&bytes[..], format!("{}:{}", serve_addr, port))
         .unwrap();
 
     assert!(server.serve().is_err());
 }
+
+#[test]
+fn test_serve_reports_why_a_request_is_invalid() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"\0\x01file\0binary\0", ("127.0.0.1", port))
+        .unwrap();
+
+    let error = match server.serve() {
+        Ok(_) => panic!("expected the request to be rejected"),
+        Err(error) => error,
+    };
+    assert!(format!("{}", error).contains("unknown transfer mode"));
+
+    let mut buf = [0; 1024];
+    let nbytes = socket.recv(&mut buf).unwrap();
+    match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+        AnyPacket::Error(error) => {
+            assert_eq!(error.body.code, Code::IllegalOperation);
+            assert!(error.body.message.contains("\"binary\""));
+        }
+        other => panic!("expected an error packet but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_serve_denies_paths_outside_of_serve_dir() {
+    let parent = tempfile::tempdir().unwrap();
+    std::fs::write(parent.path().join("secret"), b"secret").unwrap();
+    let serve_dir = parent.path().join("root");
+    std::fs::create_dir(&serve_dir).unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", &serve_dir).unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for request in &[
+        &b"\0\x01../secret\0octet\0"[..],
+        b"\0\x02../upload\0octet\0",
+    ] {
+        socket.send_to(request, ("127.0.0.1", port)).unwrap();
+
+        let error = server.serve().unwrap().handle().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+
+        let mut buf = [0; 1024];
+        let nbytes = socket.recv(&mut buf).unwrap();
+        match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+            AnyPacket::Error(error) => assert_eq!(error.body.code, Code::AccessViolation),
+            other => panic!("expected an error packet but got {:?}", other),
+        }
+    }
+    assert!(!parent.path().join("upload").exists());
+}
+
+#[test]
+fn test_serve_applies_address_rules() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server.allow_writes_from("10.0.0.0/8".parse().unwrap());
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"\0\x02upload\0octet\0", ("127.0.0.1", port))
+        .unwrap();
+    let error = match server.serve() {
+        Ok(_) => panic!("expected the request to be denied"),
+        Err(error) => error,
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+
+    let mut buf = [0; 1024];
+    let nbytes = socket.recv(&mut buf).unwrap();
+    match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+        AnyPacket::Error(error) => assert_eq!(error.body.code, Code::AccessViolation),
+        other => panic!("expected an error packet but got {:?}", other),
+    }
+
+    // Reads are not restricted.
+    socket
+        .send_to(b"\0\x01file\0octet\0", ("127.0.0.1", port))
+        .unwrap();
+    assert!(server.serve().is_ok());
+}
+
+#[test]
+fn test_serve_drops_denied_requests() {
+    let serve_dir = tempfile::tempdir().unwrap();
+    let (port, server) = Server::random_port("127.0.0.1", serve_dir.path()).unwrap();
+    let server = server
+        .deny_reads_from("127.0.0.0/8".parse().unwrap())
+        .drop_denied(true);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .send_to(b"\0\x01file\0octet\0", ("127.0.0.1", port))
+        .unwrap();
+    assert!(server.serve().is_err());
+
+    socket
+        .set_read_timeout(Some(std::time::Duration::from_millis(200)))
+        .unwrap();
+    let mut buf = [0; 1024];
+    assert!(socket.recv(&mut buf).is_err());
+}
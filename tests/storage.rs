@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::io::{self, Cursor, ErrorKind, Write};
+use std::sync::{Arc, Mutex};
+
+use tftp::packet::Mode;
+use tftp::storage::Memory;
+use tftp::{OverwritePolicy, Server, Storage};
+
+mod common;
+
+use common::{client, serve};
+
+/// Keeps files in a map, to show that a server can be backed by anything.
+#[derive(Clone, Default)]
+struct Files(Arc<Mutex<HashMap<String, Vec<u8>>>>);
+
+struct Upload {
+    name: String,
+    data: Vec<u8>,
+}
+
+impl Write for Upload {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.data.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Storage for Files {
+    type Reader = Cursor<Vec<u8>>;
+    type Writer = Upload;
+
+    fn open(&self, name: &str) -> io::Result<(Self::Reader, Option<u64>)> {
+        let files = self.0.lock().unwrap();
+        let data = files.get(name).ok_or(ErrorKind::NotFound)?.clone();
+        let size = data.len() as u64;
+        Ok((Cursor::new(data), Some(size)))
+    }
+
+    fn create(&self, name: &str, _: OverwritePolicy) -> io::Result<Self::Writer> {
+        Ok(Upload {
+            name: name.to_string(),
+            data: Vec::new(),
+        })
+    }
+
+    fn commit(&self, upload: Upload) -> io::Result<()> {
+        self.0.lock().unwrap().insert(upload.name, upload.data);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_serve_from_custom_storage() {
+    let files = Files::default();
+    files
+        .0
+        .lock()
+        .unwrap()
+        .insert("greeting".to_string(), b"hello".to_vec());
+
+    let server = Server::with_storage("127.0.0.1:0", files.clone()).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let server_thread = serve(server, 3);
+
+    let actual = client(port)
+        .get("greeting", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"hello");
+
+    let error = client(port)
+        .get("missing", Mode::Octet, Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+
+    client(port).put("reply", Mode::Octet, &b"hi"[..]).unwrap();
+    server_thread.join().unwrap();
+    assert_eq!(files.0.lock().unwrap()["reply"], b"hi");
+}
+
+#[test]
+fn test_serve_from_memory() {
+    let files = Memory::new();
+    files.insert("boot/menu.cfg", "default linux");
+
+    let server = Server::with_storage("127.0.0.1:0", files.clone()).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let server_thread = serve(server, 2);
+
+    let actual = client(port)
+        .get("/boot/menu.cfg", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"default linux");
+
+    client(port)
+        .put("backups/router.cfg", Mode::NetAscii, &b"hostname r1\n"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+    assert_eq!(
+        files.get("backups/router.cfg"),
+        Some(b"hostname r1\n".to_vec())
+    );
+}
+
+#[test]
+fn test_serve_windows_paths() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(root.path().join("Boot/PXE")).unwrap();
+    std::fs::write(root.path().join("Boot/PXE/wdsnbp.com"), b"wds").unwrap();
+
+    let server = Server::new("127.0.0.1:0", root.path())
+        .unwrap()
+        .windows_paths(true);
+    let port = server.local_addr().unwrap().port();
+    let server_thread = serve(server, 2);
+
+    let actual = client(port)
+        .get(r"\boot\pxe\WDSNBP.COM", Mode::Octet, Vec::new())
+        .unwrap();
+    assert_eq!(&actual[..], b"wds");
+
+    client(port)
+        .put(r"BOOT\pxe\log.txt", Mode::Octet, &b"booted"[..])
+        .unwrap();
+    server_thread.join().unwrap();
+    assert_eq!(
+        std::fs::read(root.path().join("Boot/PXE/log.txt")).unwrap(),
+        b"booted"
+    );
+}
@@ -0,0 +1,77 @@
+use std::net::UdpSocket;
+use std::thread;
+
+use tftp::packet::{AnyPacket, Code, Mode};
+
+mod common;
+
+use common::client;
+
+fn expect_unknown_tid(socket: &UdpSocket) {
+    let mut buf = [0; 1024];
+    let nbytes = socket.recv(&mut buf).unwrap();
+    match AnyPacket::decode(&buf[..nbytes]).unwrap() {
+        AnyPacket::Error(error) => assert_eq!(error.body.code, Code::UnknownTid),
+        other => panic!("expected an error packet but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_client_locks_onto_first_responder() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let first = UdpSocket::bind("127.0.0.1:0").unwrap();
+        first.connect(client).unwrap();
+        let second = UdpSocket::bind("127.0.0.1:0").unwrap();
+        second.connect(client).unwrap();
+
+        first.send(b"\0\x03\0\x01hello").unwrap();
+        let nbytes = first.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], &[0, 4, 0, 1]);
+
+        // Another Transfer ID trying to join in is turned away.
+        second.send(b"\0\x03\0\x01other").unwrap();
+        expect_unknown_tid(&second);
+    });
+
+    let actual = client(port).get("file", Mode::Octet, Vec::new()).unwrap();
+    assert_eq!(&actual[..], b"hello");
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_transfer_continues_after_stray_packet() {
+    let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server_thread = thread::spawn(move || {
+        let mut buf = [0; 1024];
+        let (_, client) = listener.recv_from(&mut buf).unwrap();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(client).unwrap();
+        let stranger = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stranger.connect(client).unwrap();
+
+        socket.send(&[0, 4, 0, 0]).unwrap();
+        let nbytes = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..nbytes], b"\0\x03\0\x01hello");
+
+        stranger.send(&[0, 4, 0, 1]).unwrap();
+        expect_unknown_tid(&stranger);
+
+        socket.send(&[0, 4, 0, 1]).unwrap();
+    });
+
+    client(port)
+        .put("file", Mode::Octet, &b"hello"[..])
+        .unwrap();
+
+    server_thread.join().unwrap();
+}